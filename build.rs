@@ -1,104 +1,73 @@
-#![feature(pattern)]
-
 use std::env;
 use std::fs::File;
 use std::io::Write;
+use std::iter::Peekable;
 use std::path::Path;
-use std::str::pattern::{Pattern, Searcher, SearchStep};
+use std::str::Chars;
 
-enum JSJunkState<'a> {
-    Normal,
-    InLiteral(&'a str),
+// Skips the rest of a `//` comment, up to the line break.
+fn skip_line_comment(chars: &mut Peekable<Chars>) {
+    while chars.peek().map_or(false, |&c| c != '\n') {
+        chars.next();
+    }
 }
 
-#[derive(Clone,Copy)]
-struct JSJunkSearcher<'a> {
-    haystack: &'a str,
-    pos: usize,
+// Skips the rest of a `/* */` comment, including the closing `*/`.
+fn skip_block_comment(chars: &mut Peekable<Chars>) {
+    let mut prev = ' ';
+    for c in chars.by_ref() {
+        if prev == '*' && c == '/' {
+            break;
+        }
+        prev = c;
+    }
 }
 
-unsafe impl<'a> Searcher<'a> for JSJunkSearcher<'a> {
-    fn haystack(&self) -> &'a str {
-        self.haystack
-    }
+// Replaces the comments and line breaks of the JavaScript code in `src` with spaces, so that
+// helper.js is evaluated as a single line. String literals are copied as is.
+fn strip_junk(src: &str) -> String {
+    let mut result = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    let mut literal: Option<char> = None;
 
-    fn next(&mut self) -> SearchStep {
-        eprintln!("JSJunk: next() at position {}", self.pos);
-        if self.pos >= self.haystack.len() {
-            eprintln!("JSJunk: Done!");
-            return SearchStep::Done;
+    while let Some(c) = chars.next() {
+        if let Some(quote) = literal {
+            result.push(c);
+            if c == '\\' {
+                result.extend(chars.next());
+            } else if c == quote {
+                literal = None;
+            }
+            continue;
         }
-        
-        let start = self.pos;
-        let post = self.haystack.split_at(self.pos).1;
 
-        if post.starts_with("\n") {
-            self.pos += 1;
-            eprintln!("JSJunk: Newline [[{}]]", &self.haystack[start .. self.pos - 1]);
-            SearchStep::Match(start, self.pos)
-        } else if post.starts_with("//") {
-            while self.pos < self.haystack.len() &&
-                ! self.haystack.split_at(self.pos).1.starts_with("\n") {
-                    self.pos += 1;
-                }
-            eprintln!("JSJunk: Comment [[{}]]", &self.haystack[start .. self.pos - 1]);
-            SearchStep::Match(start, self.pos)
-        } else if post.starts_with("/*") {
-            while self.pos < self.haystack.len() {
-                let newpost = self.haystack.split_at(self.pos).1;
-                if newpost.starts_with("*/") { break; }
-                self.pos += 1;
+        match c {
+            '\'' | '"' => {
+                literal = Some(c);
+                result.push(c);
             }
-            eprintln!("JSJunk: Block comment [[{}]]", &self.haystack[start .. self.pos - 1]);
-            SearchStep::Match(start, self.pos)
-        } else { // No match, skip
-            let mut state = JSJunkState::Normal;
-            while self.pos < self.haystack.len() {
-                let newpost = self.haystack.split_at(self.pos).1;
-                match state {
-                    JSJunkState::Normal => {
-                        if newpost.starts_with("'") || newpost.starts_with("\"") {
-                            state = JSJunkState::InLiteral(&self.haystack[self.pos..self.pos]);
-                        } else if newpost.starts_with("//")
-                            || newpost.starts_with("/*")
-                            || newpost.starts_with("\n") {
-                                break;
-                        }
-                    },
-                    JSJunkState::InLiteral(c) => {
-                        if newpost.starts_with(c) {
-                            state = JSJunkState::Normal;
-                        }
-                    }
-                }
-                self.pos += 1;
+            '/' if chars.peek() == Some(&'/') => {
+                skip_line_comment(&mut chars);
+                result.push(' ');
             }
-            eprintln!("JSJunk: Rejecting [[{}]]", &self.haystack[start .. self.pos - 1]);
-            SearchStep::Reject(start, self.pos)
-        }
-    }
-}
-
-struct JSJunkPattern {}
-
-impl<'a> Pattern<'a> for JSJunkPattern {
-    type Searcher = JSJunkSearcher<'a>;
-
-    fn into_searcher(self, haystack: &'a str) -> Self::Searcher {
-        JSJunkSearcher {
-            haystack: haystack,
-            pos: 0,
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                skip_block_comment(&mut chars);
+                result.push(' ');
+            }
+            '\n' => result.push(' '),
+            c => result.push(c),
         }
     }
+    result
 }
 
 fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("helper.js");
-    let mut f = File::create(&dest_path).unwrap();
+    let mut f = File::create(dest_path).unwrap();
 
-    //let helperjs_src = include_str!("src/helper.js").replace("\n", " ");
-    let helperjs_src = include_str!("src/helper.js").replace(JSJunkPattern {}, " ");
+    let helperjs_src = strip_junk(include_str!("src/helper.js"));
 
     f.write_all(helperjs_src.as_bytes()).unwrap();
-}
\ No newline at end of file
+}
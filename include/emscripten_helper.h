@@ -0,0 +1,65 @@
+/*
+ * C interface to the HELPERJS object table of emscripten-helper-rs.
+ *
+ * Rust code can hand JavaScript objects to C or C++ code linked into the
+ * same emscripten module with JSObject::into_raw() or JSObject::leak(), and
+ * take them back with JSObject::from_raw(). On the C side, a handle can be
+ * used in EM_ASM snippets with HELPERJS.loadObject($0).
+ *
+ * HELPERJS must have been initialized from Rust with init() before any of
+ * these functions are used.
+ */
+#ifndef EMSCRIPTEN_HELPER_H
+#define EMSCRIPTEN_HELPER_H
+
+#include <stdint.h>
+#include <emscripten.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * A handle to an object in the HELPERJS object table.
+ *
+ * The low 24 bits are the slot in the table and the high 8 bits are the
+ * generation of the slot when the handle was created. Generations run from
+ * 1 to 255, so 0 is never a valid handle. Once a handle is released, its
+ * slot gets a new generation and HELPERJS.loadObject rejects the old handle.
+ */
+typedef uint32_t emh_handle;
+
+#define EMH_NULL_HANDLE ((emh_handle)0)
+#define EMH_SLOT_BITS 24
+#define EMH_SLOT_MASK 0xFFFFFFu
+#define EMH_HANDLE_SLOT(handle) ((handle) & EMH_SLOT_MASK)
+#define EMH_HANDLE_GENERATION(handle) ((handle) >> EMH_SLOT_BITS)
+
+/* Whether handle still refers to an object in the table. */
+static inline int emh_is_valid(emh_handle handle) {
+    return EM_ASM_INT({ return HELPERJS.isValidHandle($0 >>> 0) ? 1 : 0; }, handle);
+}
+
+/*
+ * Store a new reference to the object behind handle and return its handle.
+ * Both handles have to be released separately.
+ */
+static inline emh_handle emh_duplicate(emh_handle handle) {
+    return (emh_handle)EM_ASM_INT({
+        return HELPERJS.storeObject(HELPERJS.loadObject($0 >>> 0));
+    }, handle);
+}
+
+/*
+ * Release handle. It must not be used afterwards, neither from C nor
+ * through a JSObject. Returns 0 if the handle was stale or already released.
+ */
+static inline int emh_release(emh_handle handle) {
+    return EM_ASM_INT({ return HELPERJS.releaseObject($0 >>> 0); }, handle);
+}
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* EMSCRIPTEN_HELPER_H */
@@ -0,0 +1,348 @@
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+use syn::{Lit, LitStr};
+
+// A literal piece of a snippet, with the span it comes from.
+struct Piece {
+    value: String,
+    span: Span,
+}
+
+// A syntax error at a byte offset in the snippet.
+struct SyntaxError {
+    offset: usize,
+    message: String,
+}
+
+pub fn expand(input: TokenStream) -> TokenStream {
+    let mut pieces = Vec::new();
+    if !collect(input.clone(), &mut pieces) || pieces.is_empty() {
+        // Not made of string literals only, e.g. `include_str!`: leave it to the compiler, unchecked.
+        return input;
+    }
+
+    let code: String = pieces.iter().map(|piece| piece.value.as_str()).collect();
+    match check(&code) {
+        Ok(()) => {
+            let code = LitStr::new(&code, pieces[0].span);
+            quote!( #code )
+        }
+        Err(error) => {
+            // Find the piece the error is in, to point at the right literal.
+            let found = pieces.iter()
+                .scan(0, |end, piece| {
+                    *end += piece.value.len();
+                    Some(*end)
+                })
+                .position(|end| error.offset < end)
+                .unwrap_or(pieces.len() - 1);
+            let start: usize = pieces[..found].iter().map(|piece| piece.value.len()).sum();
+            let piece = &pieces[found];
+            let (line, column) = position(&piece.value, error.offset - start);
+            let message = format!("JavaScript syntax error: {} (line {}, column {} of this snippet)",
+                                  error.message, line, column);
+            quote_spanned!(piece.span=> compile_error!(#message))
+        }
+    }
+}
+
+// Collects the string literals of `tokens`, which is either a string literal or `concat!` of string literals
+// and nested `concat!`s. Returns false if anything else is found.
+fn collect(tokens: TokenStream, pieces: &mut Vec<Piece>) -> bool {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Literal(literal)] => match Lit::new(literal.clone()) {
+            Lit::Str(string) => {
+                pieces.push(Piece { value: string.value(), span: string.span() });
+                true
+            }
+            _ => false,
+        },
+        // Fragments such as `$jscode:expr` arrive wrapped in an invisible group.
+        [TokenTree::Group(group)] if group.delimiter() == Delimiter::None => collect(group.stream(), pieces),
+        [TokenTree::Ident(name), TokenTree::Punct(bang), TokenTree::Group(args)]
+            if name == "concat" && bang.as_char() == '!' && args.delimiter() == Delimiter::Parenthesis =>
+        {
+            let mut arg = TokenStream::new();
+            for token in args.stream() {
+                match token {
+                    TokenTree::Punct(ref punct) if punct.as_char() == ',' => {
+                        if !collect(std::mem::take(&mut arg), pieces) {
+                            return false;
+                        }
+                    }
+                    token => arg.extend(Some(token)),
+                }
+            }
+            arg.is_empty() || collect(arg, pieces)
+        }
+        _ => false,
+    }
+}
+
+// Returns the 1-based line and column of `offset` in `code`.
+fn position(code: &str, offset: usize) -> (usize, usize) {
+    let before = &code[..offset.min(code.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |line| line.chars().count()) + 1;
+    (line, column)
+}
+
+// Words after which a `/` starts a regular expression rather than a division.
+const REGEX_PREFIX_KEYWORDS: &[&str] = &[
+    "return", "typeof", "instanceof", "in", "of", "new", "delete", "void", "throw", "case", "do", "else", "yield", "await",
+];
+
+// Words followed by a parenthesized condition, after which a `/` starts a regular expression again.
+const CONDITION_KEYWORDS: &[&str] = &["if", "while", "for", "with"];
+
+// Checks the lexical structure of `code`: strings, template literals, comments and regular expressions must
+// be terminated, and brackets must be balanced. This is not a full JavaScript parser, but it catches the
+// mistakes that are easy to make in a snippet written inside a Rust string.
+fn check(code: &str) -> Result<(), SyntaxError> {
+    let bytes = code.as_bytes();
+    // Open brackets, with their offset. A `{` opened by `${` in a template literal is stored as `$`, and the `(`
+    // of a condition such as `if (...)` as `c`.
+    let mut open: Vec<(u8, usize)> = Vec::new();
+    // Whether a `/` at this point would be a division, i.e. the previous token ends an expression.
+    let mut after_operand = false;
+    // Whether the previous token is one of `CONDITION_KEYWORDS`.
+    let mut after_condition_keyword = false;
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        let c = bytes[idx];
+        if !matches!(c, b' ' | b'\t' | b'\n' | b'\r' | 0 | b'/' | b'(') {
+            after_condition_keyword = false;
+        }
+        match c {
+            b' ' | b'\t' | b'\n' | b'\r' | 0 => idx += 1,
+            b'/' if bytes.get(idx + 1) == Some(&b'/') => {
+                while idx < bytes.len() && bytes[idx] != b'\n' {
+                    idx += 1;
+                }
+            }
+            b'/' if bytes.get(idx + 1) == Some(&b'*') => match code[idx + 2..].find("*/") {
+                Some(end) => idx += end + 4,
+                None => return Err(error(idx, "unterminated comment")),
+            },
+            b'/' if !after_operand => {
+                idx = skip_regex(bytes, idx)?;
+                after_operand = true;
+                after_condition_keyword = false;
+            }
+            b'\'' | b'"' => {
+                idx = skip_string(bytes, idx)?;
+                after_operand = true;
+            }
+            b'`' => {
+                idx = skip_template(bytes, idx + 1, idx, &mut open)?;
+                after_operand = true;
+            }
+            b'(' | b'[' | b'{' => {
+                open.push((if c == b'(' && after_condition_keyword { b'c' } else { c }, idx));
+                idx += 1;
+                after_operand = false;
+                after_condition_keyword = false;
+            }
+            b')' | b']' | b'}' => {
+                let expected = match c {
+                    b')' => b'(',
+                    b']' => b'[',
+                    _ => b'{',
+                };
+                match open.pop() {
+                    Some((b'$', start)) if c == b'}' => {
+                        // End of a `${...}` substitution: back into the template literal.
+                        idx = skip_template(bytes, idx + 1, start, &mut open)?;
+                        after_operand = true;
+                        continue;
+                    }
+                    Some((b'c', _)) if c == b')' => {
+                        // End of the condition of an `if`, `while`, ...: a statement follows.
+                        idx += 1;
+                        after_operand = false;
+                        continue;
+                    }
+                    Some((opening, _)) if opening == expected => {}
+                    // Most likely the opening bracket is the mistake, e.g. `f((x);`.
+                    Some((opening, start)) => {
+                        return Err(error(start, &format!("unclosed `{}`, found `{}` where `{}` was expected",
+                                                         opening_char(opening), c as char, closing(opening) as char)));
+                    }
+                    None => return Err(error(idx, &format!("unmatched `{}`", c as char))),
+                }
+                idx += 1;
+                after_operand = c != b'}';
+            }
+            _ if c == b'_' || c == b'$' || c.is_ascii_alphanumeric() || c >= 0x80 => {
+                let start = idx;
+                while idx < bytes.len() && (bytes[idx] == b'_' || bytes[idx] == b'$' || bytes[idx].is_ascii_alphanumeric()
+                                            || bytes[idx] >= 0x80 || (bytes[idx] == b'.' && bytes[start].is_ascii_digit())) {
+                    idx += 1;
+                }
+                after_operand = !REGEX_PREFIX_KEYWORDS.contains(&&code[start..idx]);
+                after_condition_keyword = CONDITION_KEYWORDS.contains(&&code[start..idx]);
+            }
+            // `++` and `--` leave the state unchanged: after an operand they are postfix and end an
+            // expression, otherwise they are prefix and an operand follows.
+            b'+' | b'-' if bytes.get(idx + 1) == Some(&c) => idx += 2,
+            _ => {
+                idx += 1;
+                after_operand = false;
+            }
+        }
+    }
+
+    match open.pop() {
+        Some((b'$', start)) => Err(error(start, "unterminated template literal")),
+        Some((opening, start)) => Err(error(start, &format!("unclosed `{}`", opening_char(opening)))),
+        None => Ok(()),
+    }
+}
+
+fn error(offset: usize, message: &str) -> SyntaxError {
+    SyntaxError { offset, message: message.to_string() }
+}
+
+fn closing(opening: u8) -> u8 {
+    match opening {
+        b'(' | b'c' => b')',
+        b'[' => b']',
+        _ => b'}',
+    }
+}
+
+fn opening_char(opening: u8) -> char {
+    match opening {
+        b'$' => '{',
+        b'c' => '(',
+        _ => opening as char,
+    }
+}
+
+// Skips the string literal starting at `start`, returning the offset after it.
+fn skip_string(bytes: &[u8], start: usize) -> Result<usize, SyntaxError> {
+    let quote = bytes[start];
+    let mut idx = start + 1;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' => idx += 2,
+            b'\n' => break,
+            c if c == quote => return Ok(idx + 1),
+            _ => idx += 1,
+        }
+    }
+    Err(error(start, "unterminated string literal"))
+}
+
+// Skips the template literal text from `idx` up to its end or to the next `${`, returning the offset after it.
+// A `${` is pushed onto `open`, to be closed by the matching `}`. `start` is where the template literal began.
+fn skip_template(bytes: &[u8], mut idx: usize, start: usize, open: &mut Vec<(u8, usize)>) -> Result<usize, SyntaxError> {
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' => idx += 2,
+            b'`' => return Ok(idx + 1),
+            b'$' if bytes.get(idx + 1) == Some(&b'{') => {
+                open.push((b'$', start));
+                return Ok(idx + 2);
+            }
+            _ => idx += 1,
+        }
+    }
+    Err(error(start, "unterminated template literal"))
+}
+
+// Skips the regular expression literal starting at `start` and its flags, returning the offset after them.
+fn skip_regex(bytes: &[u8], start: usize) -> Result<usize, SyntaxError> {
+    let mut idx = start + 1;
+    let mut in_class = false;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' => idx += 2,
+            b'\n' => break,
+            b'[' => {
+                in_class = true;
+                idx += 1;
+            }
+            b']' => {
+                in_class = false;
+                idx += 1;
+            }
+            b'/' if !in_class => {
+                idx += 1;
+                while idx < bytes.len() && bytes[idx].is_ascii_alphabetic() {
+                    idx += 1;
+                }
+                return Ok(idx);
+            }
+            _ => idx += 1,
+        }
+    }
+    Err(error(start, "unterminated regular expression"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_error(code: &str) -> (usize, String) {
+        let error = check(code).expect_err("expected a syntax error");
+        (error.offset, error.message)
+    }
+
+    #[test]
+    fn accepts_valid_snippets() {
+        for code in [
+            "return $0 + 1;",
+            "return \"}\" + '(' + `[${$0 + \"`\"}]`;",
+            "// a comment with ( and '\nreturn /* ) */ 1;",
+            "return /[)/]+/g.test($0);",
+            "return a / b / c;",
+            "return (a) / 2 + b[0] / 2;",
+            "var i = 0; return i++ / 2;",
+            "var i = 0; return i-- / 2 + --i;",
+            "if ($0) /[(]/.test($1);",
+            "while (x) /a'/.exec(y);",
+            "return x.length / 2 > 1 ? [] : {};",
+        ] {
+            assert!(check(code).is_ok(), "{:?} should be accepted", code);
+        }
+    }
+
+    #[test]
+    fn reports_unterminated_literals() {
+        assert_eq!(check_error("return 'abc;"), (7, "unterminated string literal".to_string()));
+        assert_eq!(check_error("return `a${b}"), (7, "unterminated template literal".to_string()));
+        assert_eq!(check_error("return 1; /* x"), (10, "unterminated comment".to_string()));
+        assert_eq!(check_error("return /abc;"), (7, "unterminated regular expression".to_string()));
+    }
+
+    #[test]
+    fn reports_unbalanced_brackets() {
+        assert_eq!(check_error("f((x);"), (1, "unclosed `(`".to_string()));
+        assert_eq!(check_error("return 1; }"), (10, "unmatched `}`".to_string()));
+        assert_eq!(check_error("if (x] {}"),
+                   (3, "unclosed `(`, found `]` where `)` was expected".to_string()));
+    }
+
+    #[test]
+    fn division_after_postfix_operator() {
+        // Lexed as a regular expression, `/ 2; (/` would leave the `(` unbalanced.
+        assert!(check("var i = 0; var x = i++ / 2; (i)").is_ok());
+    }
+
+    #[test]
+    fn errors_point_into_the_snippet() {
+        let expanded = expand(quote!("var point = {x: 1,\n  y: [2;")).to_string();
+        assert!(expanded.starts_with("compile_error !"), "{}", expanded);
+        assert!(expanded.contains("unclosed `[` (line 2, column 6 of this snippet)"), "{}", expanded);
+    }
+
+    #[test]
+    fn positions_are_one_based() {
+        assert_eq!(position("abc", 0), (1, 1));
+        assert_eq!(position("ab\ncd", 4), (2, 2));
+        assert_eq!(position("é\nx", 3), (2, 1));
+    }
+}
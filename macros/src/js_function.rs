@@ -0,0 +1,197 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Error, Expr, ExprLit, FnArg, ForeignItem, ForeignItemFn, GenericArgument, Ident, ItemForeignMod, Lit,
+    LitStr, Meta, Pat, PathArguments, ReturnType, Type,
+};
+
+// The number of arguments the js! family of macros can pass to a snippet.
+const MAX_ARGS: usize = 15;
+
+// How the result of the JavaScript function is converted for the Rust return type.
+enum Return {
+    Unit,
+    Bool,
+    Number(Type),
+    String,
+    Object,
+    Other(Type),
+}
+
+pub fn expand(attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    if attr.is_empty() {
+        let block: ItemForeignMod = syn::parse2(item)?;
+        match block.abi.name {
+            Some(ref name) if name.value() == "JS" => {}
+            _ => return Err(Error::new(block.abi.span(), "expected `extern \"JS\"`")),
+        }
+
+        let mut functions = Vec::new();
+        for item in block.items {
+            match item {
+                ForeignItem::Fn(mut decl) => {
+                    let js_name = take_js_name(&mut decl.attrs)?.unwrap_or_else(|| decl.sig.ident.to_string());
+                    let params = param_names(&decl)?;
+                    let body = format!("return {}({});", js_name, join(params.iter().map(Ident::to_string)));
+                    functions.push(function(decl, &body, Span::call_site())?);
+                }
+                other => return Err(Error::new(other.span(), "only functions can be declared in an `extern \"JS\"` block")),
+            }
+        }
+        Ok(quote!( #(#functions)* ))
+    } else {
+        let code: LitStr = syn::parse2(attr)?;
+        let decl: ForeignItemFn = syn::parse2(item)?;
+        function(decl, &code.value(), code.span())
+    }
+}
+
+// Generates a Rust function with the signature of `decl` that runs `body` as a JavaScript function.
+fn function(decl: ForeignItemFn, body: &str, span: Span) -> syn::Result<TokenStream> {
+    let ForeignItemFn { attrs, vis, sig, .. } = decl.clone();
+    if let Some(ref variadic) = sig.variadic {
+        return Err(Error::new(variadic.span(), "variadic functions cannot be implemented in JavaScript"));
+    }
+    if let Some(ref asyncness) = sig.asyncness {
+        return Err(Error::new(asyncness.span(), "async functions cannot be implemented in JavaScript"));
+    }
+
+    let params = param_names(&decl)?;
+    if params.len() > MAX_ARGS {
+        return Err(Error::new(sig.inputs.span(), format!("JavaScript functions can take at most {} arguments", MAX_ARGS)));
+    }
+    let args = (0..params.len()).map(|idx| format!("${}", idx));
+    // The body ends with a newline, so that a trailing line comment does not swallow the closing brace.
+    let call = format!("(function({}) {{ {}\n}})({})", join(params.iter().map(Ident::to_string)), body, join(args));
+
+    let (fallible, ret) = match sig.output {
+        ReturnType::Default => (false, Return::Unit),
+        ReturnType::Type(_, ref ty) => match js_result_ok_type(ty) {
+            Some(ok) => (true, return_kind(ok)),
+            None => (false, return_kind(ty)),
+        },
+    };
+
+    let code = match ret {
+        Return::Unit => format!("{};", call),
+        Return::Bool => format!("return {} ? 1 : 0;", call),
+        _ => format!("return {};", call),
+    };
+    let code = LitStr::new(&code, span);
+    let args = quote!( #(, &#params)* );
+
+    let conversion = match (fallible, ret) {
+        (false, Return::Unit) => quote!( ::emscripten_helper_rs::js!(#code #args); ),
+        (false, Return::Bool) => quote!( ::emscripten_helper_rs::js_int!(#code #args) != 0 ),
+        (false, Return::Number(ty)) => quote!( ::emscripten_helper_rs::js_double!(#code #args) as #ty ),
+        (false, Return::String) => quote!( ::emscripten_helper_rs::js_string!(#code #args) ),
+        (false, Return::Object) => quote!( ::emscripten_helper_rs::js_obj!(#code #args) ),
+        (false, Return::Other(ty)) => quote!(
+            <#ty as ::emscripten_helper_rs::FromJs>::from_js(&::emscripten_helper_rs::js_obj!(#code #args))
+        ),
+        (true, Return::Unit) => quote!( ::emscripten_helper_rs::js_try!(#code #args) ),
+        (true, Return::Bool) => quote!( ::emscripten_helper_rs::js_try_int!(#code #args).map(|result| result != 0) ),
+        (true, Return::Number(ty)) => quote!( ::emscripten_helper_rs::js_try_double!(#code #args).map(|result| result as #ty) ),
+        (true, Return::String) => quote!( ::emscripten_helper_rs::js_try_string!(#code #args) ),
+        (true, Return::Object) => quote!( ::emscripten_helper_rs::js_try_obj!(#code #args) ),
+        (true, Return::Other(ty)) => quote!(
+            ::emscripten_helper_rs::js_try_obj!(#code #args)
+                .and_then(|result| <#ty as ::emscripten_helper_rs::TryFromJs>::try_from_js(&result))
+        ),
+    };
+
+    Ok(quote!(
+        #(#attrs)*
+        #vis #sig {
+            #( let #params = ::emscripten_helper_rs::IntoJs::into_js(#params); )*
+            #conversion
+        }
+    ))
+}
+
+// Returns the names of the arguments of `decl`, which must all be plain identifiers.
+fn param_names(decl: &ForeignItemFn) -> syn::Result<Vec<Ident>> {
+    decl.sig.inputs.iter()
+        .map(|input| match *input {
+            FnArg::Typed(ref arg) => match *arg.pat {
+                Pat::Ident(ref pat) => Ok(pat.ident.clone()),
+                ref pat => Err(Error::new(pat.span(), "arguments of JavaScript functions must be plain identifiers")),
+            },
+            FnArg::Receiver(ref receiver) => Err(Error::new(receiver.span(), "JavaScript functions cannot take `self`")),
+        })
+        .collect()
+}
+
+// Removes a `#[js_name = "..."]` attribute from `attrs` and returns its value.
+fn take_js_name(attrs: &mut Vec<Attribute>) -> syn::Result<Option<String>> {
+    let mut js_name = None;
+    let mut result = Ok(());
+    attrs.retain(|attr| {
+        if !attr.path().is_ident("js_name") {
+            return true;
+        }
+        match attr.meta {
+            Meta::NameValue(ref meta) => match meta.value {
+                Expr::Lit(ExprLit { lit: Lit::Str(ref name), .. }) => js_name = Some(name.value()),
+                ref value => result = Err(Error::new(value.span(), "expected a string literal")),
+            },
+            ref meta => result = Err(Error::new(meta.span(), "expected `#[js_name = \"...\"]`")),
+        }
+        false
+    });
+    result.map(|_| js_name)
+}
+
+// If `ty` is `Result<T, JSError>`, returns `T`.
+fn js_result_ok_type(ty: &Type) -> Option<&Type> {
+    let segment = match *ty {
+        Type::Path(ref path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Result" {
+        return None;
+    }
+    let args = match segment.arguments {
+        PathArguments::AngleBracketed(ref args) if args.args.len() == 2 => &args.args,
+        _ => return None,
+    };
+    match (&args[0], &args[1]) {
+        (GenericArgument::Type(ok), GenericArgument::Type(err)) if last_ident_is(err, "JSError") => Some(ok),
+        _ => None,
+    }
+}
+
+fn return_kind(ty: &Type) -> Return {
+    if let Type::Tuple(ref tuple) = *ty {
+        if tuple.elems.is_empty() {
+            return Return::Unit;
+        }
+    }
+    let name = match *ty {
+        Type::Path(ref path) if path.qself.is_none() => match path.path.segments.last() {
+            Some(segment) if segment.arguments.is_empty() => segment.ident.to_string(),
+            _ => return Return::Other(ty.clone()),
+        },
+        _ => return Return::Other(ty.clone()),
+    };
+    match name.as_str() {
+        "bool" => Return::Bool,
+        // 64-bit integers are returned as `BigInt`s, which are converted by `FromJs`.
+        "i8" | "i16" | "i32" | "isize" | "u8" | "u16" | "u32" | "usize" | "f32" | "f64" => Return::Number(ty.clone()),
+        "String" => Return::String,
+        "JSObject" => Return::Object,
+        _ => Return::Other(ty.clone()),
+    }
+}
+
+fn last_ident_is(ty: &Type, name: &str) -> bool {
+    match *ty {
+        Type::Path(ref path) => path.path.segments.last().map_or(false, |segment| segment.ident == name),
+        _ => false,
+    }
+}
+
+fn join<I: Iterator<Item = String>>(items: I) -> String {
+    items.collect::<Vec<_>>().join(", ")
+}
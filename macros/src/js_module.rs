@@ -0,0 +1,162 @@
+use proc_macro2::{Delimiter, Group, TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{Attribute, Error, Ident, Lit, LitStr, Token, Visibility};
+
+// The number of arguments the js! family of macros can pass to a snippet.
+const MAX_ARGS: usize = 15;
+
+// `fn name(params) { body }` in a module.
+struct Function {
+    attrs: Vec<Attribute>,
+    name: Ident,
+    params: Vec<Ident>,
+    body: Group,
+}
+
+impl Parse for Function {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        input.parse::<Token![fn]>()?;
+        let name: Ident = input.parse()?;
+
+        let params_group: Group = input.parse()?;
+        if params_group.delimiter() != Delimiter::Parenthesis {
+            return Err(Error::new(params_group.span(), "expected the parameters of the function"));
+        }
+        let params = syn::parse::Parser::parse2(
+            |input: ParseStream| {
+                let params = input.parse_terminated(Ident::parse, Token![,])?;
+                Ok(params.into_iter().collect::<Vec<_>>())
+            },
+            params_group.stream(),
+        ).map_err(|error| Error::new(error.span(), "parameters of JavaScript module functions must be plain names"))?;
+        if params.len() > MAX_ARGS {
+            return Err(Error::new(params_group.span(), format!("JavaScript functions can take at most {} arguments", MAX_ARGS)));
+        }
+
+        let body: Group = input.parse()?;
+        if body.delimiter() != Delimiter::Brace {
+            return Err(Error::new(body.span(), "expected the body of the function"));
+        }
+        Ok(Function { attrs, name, params, body })
+    }
+}
+
+// `vis name { functions }`
+struct Module {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    functions: Vec<Function>,
+}
+
+impl Parse for Module {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let name = input.parse()?;
+        let content;
+        syn::braced!(content in input);
+        let mut functions = Vec::new();
+        while !content.is_empty() {
+            functions.push(content.parse()?);
+        }
+        Ok(Module { attrs, vis, name, functions })
+    }
+}
+
+pub fn expand(input: TokenStream) -> syn::Result<TokenStream> {
+    let Module { attrs, vis, name, functions } = syn::parse2(input)?;
+
+    let mut source = format!("HELPERJS.modules.{} = (function() {{\n", name);
+    for function in &functions {
+        source.push_str(&format!("function {}({}) {}\n", function.name, join(&function.params), body_source(&function.body)?));
+    }
+    let exports: Vec<String> = functions.iter().map(|function| format!("{0}: {0}", function.name)).collect();
+    source.push_str(&format!("return {{ {} }};\n}})();", exports.join(", ")));
+    let install = LitStr::new(&source, name.span());
+    let module = LitStr::new(&format!("return HELPERJS.modules.{};", name), name.span());
+    let module_name = LitStr::new(&name.to_string(), name.span());
+
+    let wrappers = functions.iter().map(|function| {
+        let Function { ref attrs, name: ref function_name, ref params, .. } = *function;
+        let args = (0..params.len()).map(|idx| format!("${}", idx)).collect::<Vec<_>>().join(", ");
+        let call = LitStr::new(&format!("return HELPERJS.modules.{}.{}({});", name, function_name, args), function_name.span());
+        let types: Vec<Ident> = (0..params.len()).map(|idx| format_ident!("__T{}", idx)).collect();
+        quote!(
+            #(#attrs)*
+            #[track_caller]
+            pub fn #function_name<#(#types: ::emscripten_helper_rs::IntoJs),*>(#(#params: #types),*) -> ::emscripten_helper_rs::JSObject {
+                ::emscripten_helper_rs::js_obj!(#call #(, ::emscripten_helper_rs::IntoJs::into_js(#params))*)
+            }
+        )
+    });
+
+    let doc = format!("Rust wrappers for the JavaScript module `{}`.", name);
+    Ok(quote!(
+        #[doc = #doc]
+        #(#attrs)*
+        #vis mod #name {
+            /// The key of the module in `HELPERJS.modules`.
+            pub const NAME: &str = #module_name;
+
+            /// Installs the module into `HELPERJS.modules`, replacing any previous version of it.
+            ///
+            /// `init()` installs every module, so this only has to be called again if `HELPERJS.modules` was
+            /// modified by hand.
+            pub fn install() {
+                ::emscripten_helper_rs::js!(#install);
+            }
+
+            /// Returns the JavaScript module, e.g. to pass it to a snippet.
+            #[track_caller]
+            pub fn module() -> ::emscripten_helper_rs::JSObject {
+                ::emscripten_helper_rs::js_obj!(#module)
+            }
+
+            ::emscripten_helper_rs::__register_module!(NAME, install);
+
+            #(#wrappers)*
+        }
+    ))
+}
+
+// Returns the JavaScript source of a function body, braces included.
+fn body_source(body: &Group) -> syn::Result<String> {
+    // A body made of a single string literal holds the code as is.
+    let tokens: Vec<TokenTree> = body.stream().into_iter().collect();
+    if let [TokenTree::Literal(literal)] = tokens.as_slice() {
+        if let Lit::Str(code) = Lit::new(literal.clone()) {
+            return Ok(format!("{{ {}\n}}", code.value()));
+        }
+    }
+    // Otherwise the original text keeps the formatting and comments of the code, but it is only available for
+    // code written in a source file, not for tokens created by a procedural macro. The tokens alone would lose comments and line breaks, which JavaScript
+    // needs to end a line comment or insert a semicolon.
+    body.span().source_text().ok_or_else(|| {
+        Error::new(body.span(), "the source of this JavaScript function is not available, e.g. because it is \
+                                 generated by a procedural macro: write its body as a string literal, such as \
+                                 `{ \"return x * x;\" }`")
+    })
+}
+
+fn join(params: &[Ident]) -> String {
+    params.iter().map(Ident::to_string).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bodies_without_source_must_be_literals() {
+        // Tokens created here have no source text, like those generated by a procedural macro.
+        let error = expand(quote!(m { fn f(x) { return x; } })).unwrap_err();
+        assert!(error.to_string().contains("write its body as a string literal"), "{}", error);
+
+        let expanded = expand(quote!(m { fn f(x) { "return x; // same" } })).unwrap().to_string();
+        assert!(expanded.contains(r#"HELPERJS.modules.m = (function() {\nfunction f(x) { return x; // same\n}\n"#),
+                "{}", expanded);
+    }
+}
@@ -0,0 +1,156 @@
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Error, Expr, Ident, LitStr, Token};
+
+// `name = value` in the arguments of a `js!` macro.
+struct NamedArg {
+    name: Ident,
+    value: Expr,
+}
+
+impl Parse for NamedArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        Ok(NamedArg { name, value })
+    }
+}
+
+// `path::to::macro [prefix], "code", name = value, ...`
+// The optional prefix holds arguments of the target macro that come before the code.
+struct Input {
+    target: TokenStream,
+    prefix: Option<TokenStream>,
+    code: LitStr,
+    args: Punctuated<NamedArg, Token![,]>,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // The target macro is passed by macro_rules as `$crate::name`, which is not a path syn can parse.
+        let mut target = TokenStream::new();
+        let mut prefix = None;
+        while !input.peek(Token![,]) {
+            match input.parse::<TokenTree>()? {
+                TokenTree::Group(ref group) if group.delimiter() == Delimiter::Bracket => prefix = Some(group.stream()),
+                token => target.extend(Some(token)),
+            }
+        }
+        input.parse::<Token![,]>()?;
+        let code = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let args = Punctuated::parse_terminated(input)?;
+        Ok(Input { target, prefix, code, args })
+    }
+}
+
+pub fn expand(input: TokenStream) -> syn::Result<TokenStream> {
+    let Input { target, prefix, code, args } = syn::parse2(input)?;
+
+    let names: Vec<&Ident> = args.iter().map(|arg| &arg.name).collect();
+    for (idx, name) in names.iter().enumerate() {
+        if names[..idx].contains(name) {
+            return Err(Error::new(name.span(), format!("duplicate argument `{}`", name)));
+        }
+    }
+
+    let (rewritten, used) = rewrite(&code.value(), &names).map_err(|msg| Error::new(code.span(), msg))?;
+    if let Some(idx) = used.iter().position(|&used| !used) {
+        return Err(Error::new(names[idx].span(), format!("argument `{}` is never used in the JavaScript snippet", names[idx])));
+    }
+
+    let code = LitStr::new(&rewritten, code.span());
+    let values = args.iter().map(|arg| &arg.value);
+    let prefix = prefix.map(|prefix| quote!( #prefix, ));
+    Ok(quote!( #target!(#prefix #code #(, #values)*) ))
+}
+
+// Replaces every `{name}` in `code` with the positional parameter of `name`, and `{{`, `}}` with single braces.
+// Returns the new code and which names were used.
+fn rewrite(code: &str, names: &[&Ident]) -> Result<(String, Vec<bool>), String> {
+    let mut result = String::with_capacity(code.len());
+    let mut used = vec![false; names.len()];
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err("unmatched `{` in JavaScript snippet, use `{{` for a literal brace".to_string()),
+                    }
+                }
+                if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit())
+                    || !name.chars().all(|c| c == '_' || c.is_alphanumeric()) {
+                    return Err(format!("expected an argument name in `{{{}}}`, use `{{{{` for a literal brace", name));
+                }
+                let idx = match names.iter().position(|known| *known == name.as_str()) {
+                    Some(idx) => idx,
+                    None => return Err(format!("unknown argument `{{{}}}` in JavaScript snippet", name)),
+                };
+                used[idx] = true;
+                result.push_str(&format!("${}", idx));
+                // Keep `{a}b` from turning into a different parameter such as `$0b` or `$01`.
+                if chars.peek().map_or(false, |&c| c == '$' || c == '_' || c.is_alphanumeric()) {
+                    result.push(' ');
+                }
+            }
+            '}' => return Err("unmatched `}` in JavaScript snippet, use `}}` for a literal brace".to_string()),
+            c => result.push(c),
+        }
+    }
+    Ok((result, used))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proc_macro2::Span;
+
+    fn rewrite_with(code: &str, names: &[&str]) -> Result<(String, Vec<bool>), String> {
+        let idents: Vec<Ident> = names.iter().map(|name| Ident::new(name, Span::call_site())).collect();
+        let idents: Vec<&Ident> = idents.iter().collect();
+        rewrite(code, &idents)
+    }
+
+    #[test]
+    fn replaces_names_with_positions() {
+        let (code, used) = rewrite_with("return {b} + {a} * {b};", &["a", "b", "c"]).unwrap();
+        assert_eq!(code, "return $1 + $0 * $1;");
+        assert_eq!(used, [true, true, false]);
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        let (code, _) = rewrite_with("if ({x}) {{ return {{ v: {x} }}; }}", &["x"]).unwrap();
+        assert_eq!(code, "if ($0) { return { v: $0 }; }");
+    }
+
+    #[test]
+    fn separates_parameter_from_following_identifier() {
+        let (code, _) = rewrite_with("return {a}1 + {a}_x + {a}.y;", &["a"]).unwrap();
+        assert_eq!(code, "return $0 1 + $0 _x + $0.y;");
+    }
+
+    #[test]
+    fn rejects_bad_placeholders() {
+        assert!(rewrite_with("return {a};", &["b"]).unwrap_err().contains("unknown argument `{a}`"));
+        assert!(rewrite_with("return { a: 1 };", &["a"]).unwrap_err().contains("expected an argument name"));
+        assert!(rewrite_with("return {1};", &["a"]).unwrap_err().contains("expected an argument name"));
+        assert!(rewrite_with("if (x) { y", &["x"]).unwrap_err().contains("unmatched `{`"));
+        assert!(rewrite_with("return a; }", &["a"]).unwrap_err().contains("unmatched `}`"));
+    }
+}
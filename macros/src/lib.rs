@@ -0,0 +1,121 @@
+/*!
+Procedural macros for [emscripten-helper-rs](https://crates.io/crates/emscripten-helper-rs).
+
+These are re-exported by emscripten-helper-rs and should be used from there.
+*/
+
+extern crate proc_macro;
+
+mod js_check;
+mod js_function;
+mod js_module;
+mod js_named;
+
+use proc_macro::TokenStream;
+
+/// Declares a Rust function implemented in JavaScript.
+///
+/// The attribute can be applied to a function declaration without a body, in which case its argument is the
+/// JavaScript body of the function. The Rust arguments are available in the snippet under their own names:
+///
+/// ```ignore
+/// #[js_function("return a + b;")]
+/// fn add(a: f64, b: f64) -> f64;
+/// ```
+///
+/// Without an argument, it can be applied to an `extern "JS"` block to call existing JavaScript functions.
+/// The JavaScript function has the same name as the Rust function, unless a `#[js_name = "..."]` attribute
+/// says otherwise:
+///
+/// ```ignore
+/// #[js_function]
+/// extern "JS" {
+///     fn alert(msg: &str);
+///     #[js_name = "console.log"]
+///     fn log(msg: &str);
+/// }
+/// ```
+///
+/// Every argument must implement `IntoJs`. The return type selects the conversion of the result:
+///
+/// * no return type: the result is ignored, like [`js!`].
+/// * `bool`: the result is converted with JavaScript truthiness.
+/// * Rust numeric types: the result is converted to a number, like [`js_double!`].
+/// * `String`: like [`js_string!`].
+/// * `JSObject`: like [`js_obj!`].
+/// * any other type `T`: converted from a `JSObject` with `FromJs`.
+/// * `Result<T, JSError>`, with `T` any of the above: exceptions thrown by the JavaScript function are
+///   returned as errors, like the `js_try` macros. Any other `T` is converted with `TryFromJs`, whose
+///   errors are returned as well.
+///
+/// [`js!`]:        ../emscripten_helper_rs/macro.js.html
+/// [`js_double!`]: ../emscripten_helper_rs/macro.js_double.html
+/// [`js_string!`]: ../emscripten_helper_rs/macro.js_string.html
+/// [`js_obj!`]:    ../emscripten_helper_rs/macro.js_obj.html
+#[proc_macro_attribute]
+pub fn js_function(attr: TokenStream, item: TokenStream) -> TokenStream {
+    js_function::expand(attr.into(), item.into())
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+/// Rewrites a `js!` family macro call with named arguments into a call with positional arguments.
+///
+/// **Should not be used directly.** It is used by the macros of emscripten-helper-rs when they are given
+/// `name = value` arguments.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __js_named(input: TokenStream) -> TokenStream {
+    js_named::expand(input.into())
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+/// Checks the syntax of a JavaScript snippet at compile time.
+///
+/// **Should not be used directly.** It is used by the macros of emscripten-helper-rs on every snippet.
+///
+/// The input is a string literal or a `concat!` of string literals, which is expanded to a single string
+/// literal. Unterminated strings, template literals, comments and regular expressions, as well as unbalanced
+/// brackets, are reported as compile errors pointing at the offending literal. Any other input, such as
+/// `include_str!`, is passed through unchecked.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __js_check(input: TokenStream) -> TokenStream {
+    js_check::expand(input.into()).into()
+}
+
+/// Declares a module of JavaScript functions, installed into `HELPERJS.modules` by `init()`, and Rust wrappers
+/// to call them.
+///
+/// ```ignore
+/// js_module!(pub geometry {
+///     /// Returns the length of the vector `(x, y)`.
+///     fn length(x, y) { return Math.sqrt(square(x) + square(y)); }
+///     fn square(x) { return x * x; }
+/// });
+///
+/// let len = f64::from(geometry::length(3, 4));
+/// ```
+///
+/// The body of each function is JavaScript, and the functions of a module can call each other by name. Since
+/// the code is read by the Rust tokenizer first, strings must use double quotes: single quotes and template
+/// literals are not valid Rust tokens. A body can also be written as a single string literal,
+/// `fn square(x) { "return x * x;" }`, which is required when the module is generated by a procedural macro,
+/// since the source of the code is not available then.
+///
+/// The module is installed as `HELPERJS.modules.<name>`, e.g. `HELPERJS.modules.geometry`, by every call to
+/// `init()`. Modules with the same name replace each other, so crates should pick distinctive names.
+///
+/// This declares a Rust module with the same name containing:
+///
+/// * one wrapper per function, taking arguments that implement `IntoJs` and returning a `JSObject`.
+/// * `NAME`, the key of the module in `HELPERJS.modules`.
+/// * `module()`, which returns the JavaScript module, to pass it to snippets: `js!("$0.square(2);", geometry::module())`.
+/// * `install()`, which installs the JavaScript module again.
+#[proc_macro]
+pub fn js_module(input: TokenStream) -> TokenStream {
+    js_module::expand(input.into())
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
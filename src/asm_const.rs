@@ -0,0 +1,363 @@
+/*!
+Typed arguments for emscripten asm-const calls.
+
+`emscripten_asm_const_int` and `emscripten_asm_const_double` take their arguments as C variadic arguments,
+described by a NUL-terminated signature string with one character per argument:
+
+* `i` - a 32-bit integer, received in JavaScript as a number.
+* `d` - a double, received in JavaScript as a number.
+* `j` - a 64-bit integer, received in JavaScript as a `BigInt`. This needs the module to be linked with
+  `-sWASM_BIGINT`, which is what `i64`, `u64` and, with `MEMORY64`, `isize` are passed as.
+* `p` - a pointer, received in JavaScript as a number. Pointers are 64 bits wide with `MEMORY64`.
+
+Every macro argument is converted with [`IntoAsmConstArg`] into an [`AsmConstArg`], which knows how it is
+passed. Its signature character follows from the native type it is passed as, so it always matches the value
+that is actually passed. The arguments are then collected in a tuple, and [`AsmConstArgs`] builds the
+signature string and performs the call.
+
+Each argument also has a kind, telling `HELPERJS.unwrapCall` how to turn it into a JavaScript value before the
+snippet runs:
+
+* `0` - the value is used as is.
+* `1` - the value is a handle, and is replaced by the object it refers to.
+* `2` - the value is `0` or `1`, and is replaced by `false` or `true`.
+* `3` - the value is a 64-bit `BigInt` holding the bits of an `u64`, and is replaced by its unsigned value.
+
+The kinds of all arguments are packed two bits each into an integer that is passed ahead of the arguments.
+
+[`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
+[`AsmConstArg`]:     trait.AsmConstArg.html
+[`AsmConstArgs`]:    trait.AsmConstArgs.html
+*/
+
+use std::os::raw::{c_char, c_double, c_int, c_void};
+
+use crate::{emscripten, IntoJs, JSObject, RefIntoJs, Repr};
+
+mod private {
+    // Types that can be passed through C variadic arguments without promotion, with the signature character
+    // telling emscripten how to read them.
+    pub trait VarArg: Copy {
+        const SIG: u8;
+    }
+
+    impl VarArg for i32 { const SIG: u8 = b'i'; }
+    impl VarArg for i64 { const SIG: u8 = b'j'; }
+    #[cfg(target_pointer_width = "64")]
+    impl VarArg for isize { const SIG: u8 = b'j'; }
+    #[cfg(not(target_pointer_width = "64"))]
+    impl VarArg for isize { const SIG: u8 = b'i'; }
+    impl VarArg for usize { const SIG: u8 = b'p'; }
+    impl VarArg for f64 { const SIG: u8 = b'd'; }
+    impl VarArg for *const std::os::raw::c_void { const SIG: u8 = b'p'; }
+}
+
+/// A value that can be passed to an emscripten asm-const call as is.
+///
+/// This is implemented for the representations emscripten understands (`i32`, `f64`, `i64`, pointers), for
+/// `u64` and `bool`, and for [`JSObject`], which is passed as a double. The value is always passed as one of
+/// these native types, and the signature character emscripten reads it with is derived from that type, so
+/// there is no way to describe an argument differently from how it is passed. To pass your own types,
+/// implement [`IntoAsmConstArg`] instead.
+///
+/// `i64` and `u64` are passed with the `j` signature character, which needs the module to be linked with
+/// `-sWASM_BIGINT`.
+///
+/// [`JSObject`]:        ../struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
+pub trait AsmConstArg {
+    /// The type passed through the C variadic arguments.
+    #[doc(hidden)]
+    type Native: private::VarArg;
+    /// Returns the value passed through the C variadic arguments.
+    #[doc(hidden)]
+    fn to_native(&self) -> Self::Native;
+    /// Returns how the value is converted on the JavaScript side, see the [module documentation](index.html).
+    #[doc(hidden)]
+    fn kind(&self) -> u32 { KIND_VALUE }
+}
+
+const KIND_VALUE: u32 = 0;
+const KIND_HANDLE: u32 = 1;
+const KIND_BOOL: u32 = 2;
+const KIND_UNSIGNED: u32 = 3;
+
+// Packs the kinds of the arguments into the integer expected by HELPERJS.unwrapCall.
+fn kind_mask(kinds: &[u32]) -> i32 {
+    kinds.iter().enumerate().fold(0, |mask, (idx, kind)| mask | (kind << (2 * idx))) as i32
+}
+
+impl AsmConstArg for i32 {
+    type Native = i32;
+    fn to_native(&self) -> i32 { *self }
+}
+
+impl AsmConstArg for f64 {
+    type Native = f64;
+    fn to_native(&self) -> f64 { *self }
+}
+
+impl AsmConstArg for i64 {
+    type Native = i64;
+    fn to_native(&self) -> i64 { *self }
+}
+
+impl AsmConstArg for u64 {
+    type Native = i64;
+    fn to_native(&self) -> i64 { *self as i64 }
+    fn kind(&self) -> u32 { KIND_UNSIGNED }
+}
+
+impl AsmConstArg for usize {
+    type Native = usize;
+    fn to_native(&self) -> usize { *self }
+}
+
+impl AsmConstArg for isize {
+    type Native = isize;
+    fn to_native(&self) -> isize { *self }
+}
+
+impl AsmConstArg for *const c_void {
+    type Native = *const c_void;
+    fn to_native(&self) -> *const c_void { *self }
+}
+
+impl AsmConstArg for bool {
+    type Native = i32;
+    fn to_native(&self) -> i32 { *self as i32 }
+    fn kind(&self) -> u32 { KIND_BOOL }
+}
+
+impl JSObject {
+    // The kind of this object as an argument.
+    pub(crate) fn kind_code(&self) -> u32 {
+        match self.repr {
+            Repr::Handle => KIND_HANDLE,
+            Repr::Number => KIND_VALUE,
+            Repr::Bool => KIND_BOOL,
+        }
+    }
+}
+
+impl AsmConstArg for JSObject {
+    type Native = f64;
+    fn to_native(&self) -> f64 { self.value }
+    fn kind(&self) -> u32 { self.kind_code() }
+}
+
+impl AsmConstArg for &JSObject {
+    type Native = f64;
+    fn to_native(&self) -> f64 { self.value }
+    fn kind(&self) -> u32 { self.kind_code() }
+}
+
+/// A type that can be used as an argument of the [`js!`] family of macros.
+///
+/// Numbers, booleans and pointers are passed in their native representation. Everything else is converted into a
+/// [`JSObject`] and passed as a handle, which is resolved to the JavaScript value before the snippet runs.
+///
+/// `i64`, `u64`, `i128` and `u128` are received as `BigInt`s. Wrap them in [`AsNumber`] to receive a number instead.
+///
+/// To pass your own type `T`, implement `std::convert::From<T> for JSObject` and then this trait with
+/// `JSObject` as `Arg`:
+///
+/// ```ignore
+/// impl IntoAsmConstArg for Point {
+///     type Arg = JSObject;
+///     fn into_asm_const_arg(self) -> JSObject { JSObject::from(self) }
+/// }
+/// ```
+///
+/// [`js!`]:      ../macro.js.html
+/// [`JSObject`]: ../struct.JSObject.html
+/// [`AsNumber`]: ../struct.AsNumber.html
+pub trait IntoAsmConstArg {
+    /// The representation passed to emscripten.
+    type Arg: AsmConstArg;
+    /// Converts the value into its representation for emscripten.
+    fn into_asm_const_arg(self) -> Self::Arg;
+}
+
+macro_rules! __into_asm_const_arg_as {
+    ( $arg:ty => $( $type:ty ),+ ) => (
+        $(
+            impl IntoAsmConstArg for $type {
+                type Arg = $arg;
+                fn into_asm_const_arg(self) -> $arg { self as $arg }
+            }
+        )+
+    )
+}
+
+__into_asm_const_arg_as!(i32 => i8, i16, i32, u8, u16);
+__into_asm_const_arg_as!(f64 => u32, f32, f64);
+__into_asm_const_arg_as!(i64 => i64);
+__into_asm_const_arg_as!(u64 => u64);
+__into_asm_const_arg_as!(usize => usize);
+__into_asm_const_arg_as!(isize => isize);
+
+impl IntoAsmConstArg for bool {
+    type Arg = bool;
+    fn into_asm_const_arg(self) -> bool { self }
+}
+
+impl<T> IntoAsmConstArg for *const T {
+    type Arg = *const c_void;
+    fn into_asm_const_arg(self) -> *const c_void { self as *const c_void }
+}
+
+impl<T> IntoAsmConstArg for *mut T {
+    type Arg = *const c_void;
+    fn into_asm_const_arg(self) -> *const c_void { self as *const c_void }
+}
+
+impl IntoAsmConstArg for JSObject {
+    type Arg = JSObject;
+    #[track_caller]
+    fn into_asm_const_arg(self) -> JSObject {
+        self.check_valid();
+        self
+    }
+}
+
+impl<'a> IntoAsmConstArg for &'a JSObject {
+    type Arg = &'a JSObject;
+    #[track_caller]
+    fn into_asm_const_arg(self) -> &'a JSObject {
+        self.check_valid();
+        self
+    }
+}
+
+macro_rules! __into_asm_const_arg_via_jsobject {
+    ( $( $type:ty ),+ ) => (
+        $(
+            impl<'a> IntoAsmConstArg for $type {
+                type Arg = JSObject;
+                #[track_caller]
+                fn into_asm_const_arg(self) -> JSObject { JSObject::from(self) }
+            }
+        )+
+    )
+}
+
+__into_asm_const_arg_via_jsobject!(&'a str, &'a String, String, i128, u128);
+
+impl<T: IntoJs> IntoAsmConstArg for Vec<T> {
+    type Arg = JSObject;
+    #[track_caller]
+    fn into_asm_const_arg(self) -> JSObject { JSObject::from(self) }
+}
+
+impl<T: RefIntoJs> IntoAsmConstArg for &[T] {
+    type Arg = JSObject;
+    #[track_caller]
+    fn into_asm_const_arg(self) -> JSObject { JSObject::from(self) }
+}
+
+impl<T: RefIntoJs> IntoAsmConstArg for &Vec<T> {
+    type Arg = JSObject;
+    #[track_caller]
+    fn into_asm_const_arg(self) -> JSObject { JSObject::from(self) }
+}
+
+/// A tuple of [`AsmConstArg`]s that can be passed to an emscripten asm-const call.
+///
+/// Implemented for tuples of up to 15 elements. Used by the [`js!`] family of macros.
+///
+/// [`AsmConstArg`]: trait.AsmConstArg.html
+/// [`js!`]:         ../macro.js.html
+pub trait AsmConstArgs {
+    /// Runs `code` with these arguments through `emscripten_asm_const_int`.
+    ///
+    /// `code` receives the [kinds](index.html) of the arguments as `$0`, followed by the arguments themselves.
+    ///
+    /// # Safety
+    ///
+    /// `code` must be NUL-terminated.
+    unsafe fn emscripten_asm_const_int(&self, code: &'static str) -> c_int;
+
+    /// Runs `code` with these arguments through `emscripten_asm_const_double`.
+    ///
+    /// `code` receives the [kinds](index.html) of the arguments as `$0`, followed by the arguments themselves.
+    ///
+    /// # Safety
+    ///
+    /// `code` must be NUL-terminated.
+    unsafe fn emscripten_asm_const_double(&self, code: &'static str) -> c_double;
+}
+
+macro_rules! __asm_const_args_tuple {
+    ( $( $type:ident $name:ident ),* ) => (
+        impl<$( $type: AsmConstArg ),*> AsmConstArgs for ($( $type, )*) {
+            unsafe fn emscripten_asm_const_int(&self, code: &'static str) -> c_int {
+                let ($( ref $name, )*) = *self;
+                let arg_sigs = [b'i', $( <$type::Native as private::VarArg>::SIG, )* 0];
+                emscripten::emscripten_asm_const_int(code.as_ptr() as *const c_char, arg_sigs.as_ptr() as *const c_char,
+                                                     kind_mask(&[$( $name.kind() ),*]) $(, $name.to_native() )*)
+            }
+
+            unsafe fn emscripten_asm_const_double(&self, code: &'static str) -> c_double {
+                let ($( ref $name, )*) = *self;
+                let arg_sigs = [b'i', $( <$type::Native as private::VarArg>::SIG, )* 0];
+                emscripten::emscripten_asm_const_double(code.as_ptr() as *const c_char, arg_sigs.as_ptr() as *const c_char,
+                                                        kind_mask(&[$( $name.kind() ),*]) $(, $name.to_native() )*)
+            }
+        }
+    )
+}
+
+__asm_const_args_tuple!();
+__asm_const_args_tuple!(A a);
+__asm_const_args_tuple!(A a, B b);
+__asm_const_args_tuple!(A a, B b, C c);
+__asm_const_args_tuple!(A a, B b, C c, D d);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e, F f);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e, F f, G g);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e, F f, G g, H h);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n);
+__asm_const_args_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n, O o);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use private::VarArg;
+
+    fn sig<T: AsmConstArg>() -> u8 {
+        <T::Native as VarArg>::SIG
+    }
+
+    #[test]
+    fn signature_follows_native_type() {
+        assert_eq!(sig::<i32>(), b'i');
+        assert_eq!(sig::<bool>(), b'i');
+        assert_eq!(sig::<f64>(), b'd');
+        assert_eq!(sig::<JSObject>(), b'd');
+        assert_eq!(sig::<&JSObject>(), b'd');
+        assert_eq!(sig::<i64>(), b'j');
+        assert_eq!(sig::<u64>(), b'j');
+        assert_eq!(sig::<usize>(), b'p');
+        assert_eq!(sig::<*const c_void>(), b'p');
+    }
+
+    #[test]
+    fn kinds_are_packed_two_bits_per_argument() {
+        assert_eq!(kind_mask(&[]), 0);
+        assert_eq!(kind_mask(&[KIND_HANDLE]), 0b01);
+        assert_eq!(kind_mask(&[KIND_VALUE, KIND_BOOL, KIND_HANDLE]), 0b01_10_00);
+        assert_eq!(kind_mask(&[KIND_UNSIGNED; 15]), 0x3FFF_FFFF);
+    }
+
+    #[test]
+    fn kinds_of_arguments() {
+        assert_eq!(kind_mask(&[1.5.kind(), true.kind(), 7u64.kind(), AsmConstArg::kind(&JSObject::from(false))]),
+                   0b10_11_10_00);
+    }
+}
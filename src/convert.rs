@@ -0,0 +1,756 @@
+/*!
+Conversions between Rust values and JavaScript values.
+
+* [`IntoJs`] converts a value into a [`JSObject`], consuming it. It is implemented for every type `T`
+  with `std::convert::From<T> for JSObject`.
+* [`RefIntoJs`] converts a borrowed value into a [`JSObject`]. Every type implementing it can also be
+  converted by reference with `std::convert::From<&T> for JSObject`.
+* [`FromJs`] converts the JavaScript value referred to by a borrowed [`JSObject`] into a Rust value.
+* [`TryFromJs`] is the fallible version of [`FromJs`]. It checks the type of the JavaScript value first, so that
+  e.g. converting a number into a `String` fails instead of calling `String()` on it.
+
+`i64`, `u64`, `i128` and `u128` are converted to and from JavaScript `BigInt`s, so that they keep their exact
+value. Converting a `BigInt` that does not fit wraps around like an `as` cast, and values that are not `BigInt`s
+are converted like for the other numeric types. Wrap these integers in [`AsNumber`] to convert them to and from
+JavaScript numbers instead, losing precision above 2<sup>53</sup>.
+
+Slices and `Vec`s of `i8`, `u8`, `i16`, `u16`, `i32`, `u32`, `f32` and `f64`, borrowed or passed by value, are
+converted into the matching typed arrays, e.g. `Float32Array`, with a single copy. `i64` and `u64` use
+`BigInt64Array` and `BigUint64Array`. Other slices and `Vec`s are converted into JavaScript arrays. In the other
+direction, a `Vec` of these numeric types is copied from any typed array at once, converting each element as
+`TypedArray.prototype.set` does.
+
+`Vec<T>` is converted from any JavaScript array or iterable, such as a `Set` or a `NodeList`, as long as every
+element can be converted into `T`. The error tells which element could not be converted.
+
+`Option<T>` is converted to `null` when it is `None`, and from both `null` and `undefined` to `None`. Use
+[`Nullish`] to tell `null` and `undefined` apart, or to pass `undefined`.
+
+The `std::convert::From` implementations between [`JSObject`] and the types of this crate are implemented
+with these traits. To make your own types convertible, implement [`RefIntoJs`] or [`IntoJs`] and [`FromJs`] and
+[`TryFromJs`] for them.
+
+[`IntoJs`]:    trait.IntoJs.html
+[`RefIntoJs`]: trait.RefIntoJs.html
+[`FromJs`]:    trait.FromJs.html
+[`TryFromJs`]: trait.TryFromJs.html
+[`JSObject`]:  struct.JSObject.html
+[`AsNumber`]:  struct.AsNumber.html
+[`Nullish`]:   enum.Nullish.html
+*/
+
+use crate::{AsmConstArgs, IntoAsmConstArg, JSError, JSObject, JSKind};
+
+/// A type that can be converted into a [`JSObject`], consuming it.
+///
+/// Implemented for every type `T` where `std::convert::From<T> for JSObject` is implemented, which includes
+/// all types implementing [`RefIntoJs`] when passed by reference.
+///
+/// [`JSObject`]:  struct.JSObject.html
+/// [`RefIntoJs`]: trait.RefIntoJs.html
+pub trait IntoJs {
+    /// Converts the value into a [`JSObject`].
+    ///
+    /// [`JSObject`]: struct.JSObject.html
+    fn into_js(self) -> JSObject;
+}
+
+impl<T> IntoJs for T where JSObject: From<T> {
+    #[track_caller]
+    fn into_js(self) -> JSObject {
+        JSObject::from(self)
+    }
+}
+
+/// A type that can be converted into a [`JSObject`] without giving it up.
+///
+/// `std::convert::From<&T> for JSObject` is implemented for every type `T` implementing this trait.
+///
+/// [`JSObject`]: struct.JSObject.html
+pub trait RefIntoJs {
+    /// Converts the value into a [`JSObject`].
+    ///
+    /// [`JSObject`]: struct.JSObject.html
+    fn to_js(&self) -> JSObject;
+
+    /// Converts a slice of values into a [`JSObject`].
+    ///
+    /// **Should not be used directly.** It lets numeric types convert slices into typed arrays.
+    ///
+    /// [`JSObject`]: struct.JSObject.html
+    #[doc(hidden)]
+    #[track_caller]
+    fn __slice_to_js(slice: &[Self]) -> JSObject where Self: Sized {
+        let objects: Vec<JSObject> = slice.iter().map(RefIntoJs::to_js).collect();
+        crate::array_from_objects(&objects)
+    }
+}
+
+impl RefIntoJs for JSObject {
+    #[track_caller]
+    fn to_js(&self) -> JSObject {
+        self.check_valid();
+        self.clone()
+    }
+}
+
+impl RefIntoJs for str {
+    #[track_caller]
+    fn to_js(&self) -> JSObject {
+        let data : Vec<u16> = self.encode_utf16().collect();
+        let handle = js_int!("return HELPERJS.storeObject(HELPERJS.copyStringFromHeap($0, $1));", data.as_ptr(), data.len() as f64) as u32;
+        JSObject::__from_handle(handle)
+    }
+}
+
+impl RefIntoJs for String {
+    #[track_caller]
+    fn to_js(&self) -> JSObject {
+        self.as_str().to_js()
+    }
+}
+
+impl<T: RefIntoJs> RefIntoJs for [T] {
+    #[track_caller]
+    fn to_js(&self) -> JSObject {
+        T::__slice_to_js(self)
+    }
+}
+
+impl<T: RefIntoJs> RefIntoJs for Vec<T> {
+    #[track_caller]
+    fn to_js(&self) -> JSObject {
+        self.as_slice().to_js()
+    }
+}
+
+impl<T: RefIntoJs + ?Sized> RefIntoJs for &T {
+    #[track_caller]
+    fn to_js(&self) -> JSObject {
+        (**self).to_js()
+    }
+}
+
+impl RefIntoJs for bool {
+    fn to_js(&self) -> JSObject {
+        JSObject::from(*self)
+    }
+}
+
+/// A type that can be converted from a [`JSObject`], and returned by a JavaScript snippet with [`js_as!`].
+///
+/// Implemented for every type that can be converted from a [`JSObject`] by this crate. Numbers and
+/// booleans are returned directly by [`js_as!`] snippets, without storing them in the object table first.
+///
+/// To convert your own type, implement [`FromJs::from_js`]:
+///
+/// ```ignore
+/// impl FromJs for Point {
+///     fn from_js(obj: &JSObject) -> Self {
+///         Point { x: obj.get("x"), y: obj.get("y") }
+///     }
+/// }
+///
+/// let origin: Point = js_as!(Point, "return {x: 0, y: 0};");
+/// ```
+///
+/// [`js_as!`]:           macro.js_as.html
+/// [`JSObject`]:         struct.JSObject.html
+/// [`FromJs::from_js`]:  trait.FromJs.html#tymethod.from_js
+pub trait FromJs: Sized {
+    /// Converts the JavaScript value referred to by `obj`.
+    fn from_js(obj: &JSObject) -> Self;
+
+    /// Runs `code` with `args` and converts its result.
+    ///
+    /// **Should not be used directly.** It is only public so that it can be used by [`js_as!`].
+    ///
+    /// # Safety
+    ///
+    /// The strings in `code` must be NUL-terminated snippets built by [`js_as!`].
+    ///
+    /// [`js_as!`]: macro.js_as.html
+    #[doc(hidden)]
+    #[track_caller]
+    unsafe fn __eval<A: AsmConstArgs>(code: &JsCode, args: &A) -> Self {
+        Self::from_js(&JSObject::__from_handle(args.emscripten_asm_const_int(code.object) as u32))
+    }
+}
+
+/// A type that can be converted from a [`JSObject`], when not every JavaScript value is acceptable.
+///
+/// The types of this crate check the type of the JavaScript value before converting it, and fail with a
+/// `TypeError` when it does not match: numeric types expect a number, `bool` a boolean and `String` a string.
+/// `i64`, `u64`, `i128` and `u128` also accept a `BigInt`, and `JSObject` accepts anything. [`FromJs`] converts
+/// the same values without checking, e.g. with `String()` or `Number()`.
+///
+/// It is not implemented automatically for types implementing [`FromJs`]:
+///
+/// ```ignore
+/// impl TryFromJs for Point {
+///     fn try_from_js(obj: &JSObject) -> Result<Self, JSError> {
+///         Ok(Point { x: f64::try_from_js(&obj.get("x"))?, y: f64::try_from_js(&obj.get("y"))? })
+///     }
+/// }
+/// ```
+///
+/// [`JSObject`]: struct.JSObject.html
+/// [`FromJs`]:   trait.FromJs.html
+pub trait TryFromJs: Sized {
+    /// Converts the JavaScript value referred to by `obj`.
+    ///
+    /// # Return value
+    ///
+    /// The converted value, or a [`JSError`] describing why `obj` could not be converted.
+    ///
+    /// [`JSError`]: struct.JSError.html
+    fn try_from_js(obj: &JSObject) -> Result<Self, JSError>;
+
+    /// Converts the typed array referred to by `obj` in a single copy, or returns `None` to convert it
+    /// element by element.
+    ///
+    /// **Should not be used directly.** It lets numeric types convert typed arrays into `Vec`s.
+    #[doc(hidden)]
+    fn __vec_from_js(_obj: &JSObject) -> Option<Vec<Self>> {
+        None
+    }
+}
+
+// Checks that `obj` is one of the `expected` kinds, or returns a `TypeError` telling that `what` was expected.
+#[track_caller]
+fn expect_kind(obj: &JSObject, expected: &[JSKind], what: &str) -> Result<(), JSError> {
+    let kind = obj.kind();
+    if expected.contains(&kind) {
+        Ok(())
+    } else {
+        Err(JSError {
+            name: "TypeError".to_string(),
+            message: format!("expected {}, found {}", what, kind.describe()),
+            stack: String::new(),
+        })
+    }
+}
+
+// Tells which element of an array `error` comes from.
+fn element_error(idx: u32, error: JSError) -> JSError {
+    JSError {
+        message: format!("element {}: {}", idx, error.message),
+        ..error
+    }
+}
+
+/// The code of a [`js_as!`] snippet, wrapped for each way of returning its result.
+///
+/// **Should not be used directly.** It is only public so that it can be used by [`js_as!`].
+///
+/// [`js_as!`]: macro.js_as.html
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy)]
+pub struct JsCode {
+    /// The snippet as is, for results returned as numbers.
+    pub value: &'static str,
+    /// The snippet with its result stored in the [`HELPERJS`] object table, for results returned as handles.
+    ///
+    /// [`HELPERJS`]: index.html#emscripten-helper-global
+    pub object: &'static str,
+    /// The snippet with its result converted to `1` or `0` following JavaScript truthiness, for booleans.
+    pub boolean: &'static str,
+}
+
+impl FromJs for JSObject {
+    fn from_js(obj: &JSObject) -> Self {
+        obj.clone()
+    }
+}
+
+impl TryFromJs for JSObject {
+    fn try_from_js(obj: &JSObject) -> Result<Self, JSError> {
+        Ok(obj.clone())
+    }
+}
+
+impl FromJs for () {
+    fn from_js(_obj: &JSObject) -> Self {}
+
+    unsafe fn __eval<A: AsmConstArgs>(code: &JsCode, args: &A) -> Self {
+        args.emscripten_asm_const_int(code.value);
+    }
+}
+
+impl TryFromJs for () {
+    fn try_from_js(_obj: &JSObject) -> Result<Self, JSError> {
+        Ok(())
+    }
+}
+
+// Follows JavaScript truthiness, like `#[js_function]` functions returning `bool`.
+impl FromJs for bool {
+    fn from_js(obj: &JSObject) -> Self {
+        if obj.is_handle() {
+            js_int!("return $0 ? 1 : 0;",
+                    obj) != 0
+        } else {
+            obj.value != 0f64 && !obj.value.is_nan()
+        }
+    }
+
+    unsafe fn __eval<A: AsmConstArgs>(code: &JsCode, args: &A) -> Self {
+        args.emscripten_asm_const_int(code.boolean) != 0
+    }
+}
+
+impl TryFromJs for bool {
+    #[track_caller]
+    fn try_from_js(obj: &JSObject) -> Result<Self, JSError> {
+        expect_kind(obj, &[JSKind::Bool], "a boolean")?;
+        Ok(bool::from_js(obj))
+    }
+}
+
+impl FromJs for String {
+    fn from_js(obj: &JSObject) -> Self {
+        let address = js_double!("return HELPERJS.copyStringToHeap($0);", obj);
+        crate::string_from_js(address)
+    }
+}
+
+impl TryFromJs for String {
+    #[track_caller]
+    fn try_from_js(obj: &JSObject) -> Result<Self, JSError> {
+        expect_kind(obj, &[JSKind::String], "a string")?;
+        Ok(String::from_js(obj))
+    }
+}
+
+// Element types of typed arrays. The codes are indices into HELPERJS.TYPED_ARRAYS, so keep both in sync.
+const INT8_ARRAY: i32 = 0;
+const UINT8_ARRAY: i32 = 1;
+const INT16_ARRAY: i32 = 2;
+const UINT16_ARRAY: i32 = 3;
+const INT32_ARRAY: i32 = 4;
+const UINT32_ARRAY: i32 = 5;
+const FLOAT32_ARRAY: i32 = 6;
+const FLOAT64_ARRAY: i32 = 7;
+const BIGINT64_ARRAY: i32 = 8;
+const BIGUINT64_ARRAY: i32 = 9;
+
+mod private {
+    // Numeric types with a matching typed array.
+    pub trait Sealed {}
+}
+
+/// A numeric type with a matching JavaScript typed array, e.g. `f32` and `Float32Array`.
+///
+/// Implemented for `i8`, `u8`, `i16`, `u16`, `i32`, `u32`, `f32`, `f64`, `i64` and `u64`. It cannot be implemented
+/// outside of this crate. Used by [`HeapView`].
+///
+/// [`HeapView`]: struct.HeapView.html
+pub trait TypedArrayElement: private::Sealed {
+    /// The index of the typed array constructor in `HELPERJS.TYPED_ARRAYS`.
+    #[doc(hidden)]
+    const TYPED_ARRAY: i32;
+}
+
+// The index in `HELPERJS.TYPED_ARRAYS` of the typed array matching `T`, if `T` implements `TypedArrayElement`.
+// Code that is generic over `IntoJs` cannot ask `T` itself, and `TypeId` would need `T: 'static`, so `T` is
+// compared by name with the element types, whose names cannot be taken by any other type.
+pub(crate) fn typed_array_of<T>() -> Option<i32> {
+    fn element<E: TypedArrayElement>() -> (&'static str, i32) {
+        (std::any::type_name::<E>(), E::TYPED_ARRAY)
+    }
+    let elements = [element::<i8>(), element::<u8>(), element::<i16>(), element::<u16>(), element::<i32>(),
+                    element::<u32>(), element::<f32>(), element::<f64>(), element::<i64>(), element::<u64>()];
+    let name = std::any::type_name::<T>();
+    elements.iter().find(|&&(element, _)| element == name).map(|&(_, array)| array)
+}
+
+macro_rules! __js_typed_array_element {
+    ( $type:ty, $array:expr ) => (
+        impl private::Sealed for $type {}
+
+        impl TypedArrayElement for $type {
+            const TYPED_ARRAY: i32 = $array;
+        }
+    )
+}
+
+// Implements the hidden slice methods of RefIntoJs and TryFromJs for a numeric type, copying it to and from
+// typed arrays of the given element type.
+macro_rules! __js_typed_array_methods {
+    ( into, $type:ty, $array:expr ) => (
+        #[track_caller]
+        fn __slice_to_js(slice: &[Self]) -> JSObject {
+            js_obj!("return HELPERJS.copyArrayFromHeap($0, $1, $2);",
+                    $array, slice.as_ptr(), slice.len())
+        }
+    );
+    ( from, $type:ty, $array:expr ) => (
+        #[track_caller]
+        fn __vec_from_js(obj: &JSObject) -> Option<Vec<Self>> {
+            if !obj.is_handle() {
+                return None;
+            }
+            let len = js_int!("return HELPERJS.typedArrayLength($0, $1);",
+                              obj, $array);
+            if len < 0 {
+                return None;
+            }
+            let mut vec = Vec::<$type>::with_capacity(len as usize);
+            let copied = js_int!("return HELPERJS.copyArrayToHeap($0, $1, $2, $3);",
+                                 $array, obj, vec.as_mut_ptr(), len);
+            // The typed array cannot grow, but it could have been detached since its length was read.
+            unsafe { vec.set_len(copied as usize) };
+            Some(vec)
+        }
+    );
+}
+
+macro_rules! __js_convert_numeric {
+    ( $( $type:ty $( => $array:expr )? ),+ ) => (
+        $(
+            impl RefIntoJs for $type {
+                fn to_js(&self) -> JSObject {
+                    JSObject::from(*self)
+                }
+
+                $( __js_typed_array_methods!(into, $type, $array); )?
+            }
+
+            impl FromJs for $type {
+                fn from_js(obj: &JSObject) -> Self {
+                    if obj.is_handle() {
+                        js_double!("return Number($0);",
+                                   obj) as $type
+                    } else {
+                        obj.value as $type
+                    }
+                }
+
+                unsafe fn __eval<A: AsmConstArgs>(code: &JsCode, args: &A) -> Self {
+                    args.emscripten_asm_const_double(code.value) as $type
+                }
+            }
+
+            impl TryFromJs for $type {
+                #[track_caller]
+                fn try_from_js(obj: &JSObject) -> Result<Self, JSError> {
+                    expect_kind(obj, &[JSKind::Number], "a number")?;
+                    Ok(<$type>::from_js(obj))
+                }
+
+                $( __js_typed_array_methods!(from, $type, $array); )?
+            }
+
+            $( __js_typed_array_element!($type, $array); )?
+        )+
+    )
+}
+
+__js_convert_numeric!(isize, usize,
+                      i32 => INT32_ARRAY, u32 => UINT32_ARRAY,
+                      i16 => INT16_ARRAY, u16 => UINT16_ARRAY,
+                      i8 => INT8_ARRAY, u8 => UINT8_ARRAY,
+                      f32 => FLOAT32_ARRAY, f64 => FLOAT64_ARRAY);
+
+// Reads the low 128 bits of the value referred to by `obj`, in two's complement, or returns `None` if it is not
+// a `BigInt`.
+#[track_caller]
+fn bigint_bits(obj: &JSObject) -> Option<u128> {
+    let mut words = [0u32; 4];
+    let stored = js_int!("return HELPERJS.storeBigInt($0, $1, $2);",
+                         obj, words.as_mut_ptr(), words.len());
+    if stored == 0 {
+        return None;
+    }
+    Some(bits_from_words(&words))
+}
+
+// Assembles the 32-bit words written by `HELPERJS.storeBigInt`, least significant first.
+fn bits_from_words(words: &[u32; 4]) -> u128 {
+    words.iter().rev().fold(0, |bits, &word| (bits << 32) | word as u128)
+}
+
+macro_rules! __js_convert_bigint {
+    ( $( $type:ty $( => $array:expr )? ),+ ) => (
+        $(
+            impl RefIntoJs for $type {
+                #[track_caller]
+                fn to_js(&self) -> JSObject {
+                    JSObject::from(*self)
+                }
+
+                $( __js_typed_array_methods!(into, $type, $array); )?
+            }
+
+            impl FromJs for $type {
+                #[track_caller]
+                fn from_js(obj: &JSObject) -> Self {
+                    if !obj.is_handle() {
+                        return obj.value as $type;
+                    }
+                    match bigint_bits(obj) {
+                        Some(bits) => bits as $type,
+                        None => js_double!("return Number($0);",
+                                           obj) as $type,
+                    }
+                }
+            }
+
+            impl TryFromJs for $type {
+                #[track_caller]
+                fn try_from_js(obj: &JSObject) -> Result<Self, JSError> {
+                    expect_kind(obj, &[JSKind::BigInt, JSKind::Number], "a BigInt or a number")?;
+                    Ok(<$type>::from_js(obj))
+                }
+
+                $( __js_typed_array_methods!(from, $type, $array); )?
+            }
+
+            $( __js_typed_array_element!($type, $array); )?
+
+            impl RefIntoJs for AsNumber<$type> {
+                fn to_js(&self) -> JSObject {
+                    JSObject::from(*self)
+                }
+            }
+
+            impl FromJs for AsNumber<$type> {
+                fn from_js(obj: &JSObject) -> Self {
+                    AsNumber(f64::from_js(obj) as $type)
+                }
+
+                unsafe fn __eval<A: AsmConstArgs>(code: &JsCode, args: &A) -> Self {
+                    AsNumber(f64::__eval(code, args) as $type)
+                }
+            }
+
+            impl TryFromJs for AsNumber<$type> {
+                #[track_caller]
+                fn try_from_js(obj: &JSObject) -> Result<Self, JSError> {
+                    f64::try_from_js(obj).map(|value| AsNumber(value as $type))
+                }
+            }
+
+            impl IntoAsmConstArg for AsNumber<$type> {
+                type Arg = f64;
+                fn into_asm_const_arg(self) -> f64 { self.0 as f64 }
+            }
+        )+
+    )
+}
+
+__js_convert_bigint!(i64 => BIGINT64_ARRAY, u64 => BIGUINT64_ARRAY, i128, u128);
+
+/// A 64-bit or 128-bit integer converted to and from a JavaScript number rather than a `BigInt`.
+///
+/// Numbers are doubles in JavaScript, so integers above 2<sup>53</sup> lose precision. Use this when the
+/// JavaScript code expects numbers and the values are known to be small enough, e.g. sizes and counters:
+///
+/// ```ignore
+/// let count: AsNumber<u64> = js_as!(AsNumber<u64>, "return $0.length;", &array);
+/// js!("$0.fill(0, 0, $1);", &array, AsNumber(count.0 / 2));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct AsNumber<T>(pub T);
+
+impl<T: RefIntoJs> RefIntoJs for Option<T> {
+    #[track_caller]
+    fn to_js(&self) -> JSObject {
+        match *self {
+            Some(ref value) => value.to_js(),
+            None => JSObject::null(),
+        }
+    }
+}
+
+impl<T: FromJs> FromJs for Option<T> {
+    #[track_caller]
+    fn from_js(obj: &JSObject) -> Self {
+        match obj.kind() {
+            JSKind::Null | JSKind::Undefined => None,
+            _ => Some(T::from_js(obj)),
+        }
+    }
+}
+
+impl<T: TryFromJs> TryFromJs for Option<T> {
+    #[track_caller]
+    fn try_from_js(obj: &JSObject) -> Result<Self, JSError> {
+        match obj.kind() {
+            JSKind::Null | JSKind::Undefined => Ok(None),
+            _ => T::try_from_js(obj).map(Some),
+        }
+    }
+}
+
+impl<T: IntoJs> IntoAsmConstArg for Option<T> {
+    type Arg = JSObject;
+    #[track_caller]
+    fn into_asm_const_arg(self) -> JSObject { JSObject::from(self) }
+}
+
+/// An optional JavaScript value that tells `null` and `undefined` apart.
+///
+/// `Option<T>` converts both `null` and `undefined` into `None`, and `None` into `null`. Use this type instead
+/// when the difference matters, e.g. to tell a property set to `null` from a missing one:
+///
+/// ```ignore
+/// match js_as!(Nullish<String>, "return $0.title;", &config) {
+///     Nullish::Undefined => println!("no title"),
+///     Nullish::Null => println!("title removed"),
+///     Nullish::Value(title) => println!("title: {}", title),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Nullish<T> {
+    Undefined,
+    Null,
+    Value(T),
+}
+
+impl<T> Nullish<T> {
+    /// Converts into an `Option`, with both `null` and `undefined` becoming `None`.
+    pub fn into_option(self) -> Option<T> {
+        match self {
+            Nullish::Value(value) => Some(value),
+            Nullish::Undefined | Nullish::Null => None,
+        }
+    }
+}
+
+impl<T> From<Option<T>> for Nullish<T> {
+    fn from(option: Option<T>) -> Self {
+        match option {
+            Some(value) => Nullish::Value(value),
+            None => Nullish::Null,
+        }
+    }
+}
+
+impl<T: RefIntoJs> RefIntoJs for Nullish<T> {
+    #[track_caller]
+    fn to_js(&self) -> JSObject {
+        match *self {
+            Nullish::Undefined => JSObject::undefined(),
+            Nullish::Null => JSObject::null(),
+            Nullish::Value(ref value) => value.to_js(),
+        }
+    }
+}
+
+impl<T: FromJs> FromJs for Nullish<T> {
+    #[track_caller]
+    fn from_js(obj: &JSObject) -> Self {
+        match obj.kind() {
+            JSKind::Undefined => Nullish::Undefined,
+            JSKind::Null => Nullish::Null,
+            _ => Nullish::Value(T::from_js(obj)),
+        }
+    }
+}
+
+impl<T: TryFromJs> TryFromJs for Nullish<T> {
+    #[track_caller]
+    fn try_from_js(obj: &JSObject) -> Result<Self, JSError> {
+        match obj.kind() {
+            JSKind::Undefined => Ok(Nullish::Undefined),
+            JSKind::Null => Ok(Nullish::Null),
+            _ => T::try_from_js(obj).map(Nullish::Value),
+        }
+    }
+}
+
+impl<T: IntoJs> IntoAsmConstArg for Nullish<T> {
+    type Arg = JSObject;
+    #[track_caller]
+    fn into_asm_const_arg(self) -> JSObject { JSObject::from(self) }
+}
+
+impl<T: TryFromJs> TryFromJs for Vec<T> {
+    #[track_caller]
+    fn try_from_js(obj: &JSObject) -> Result<Self, JSError> {
+        if let Some(vec) = <T as TryFromJs>::__vec_from_js(obj) {
+            return Ok(vec);
+        }
+        let array = js_try_obj!("return HELPERJS.toArray($0);", obj)?;
+        let len = js_int!("return $0.length;", &array) as u32;
+        let mut vec = Vec::with_capacity(len as usize);
+        for idx in 0..len {
+            let elem = js_obj!("return $0[$1];", &array, idx);
+            let value = T::try_from_js(&elem).map_err(|error| element_error(idx, error))?;
+            vec.push(value);
+        }
+        Ok(vec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_arrays_of_element_types() {
+        assert_eq!(typed_array_of::<u8>(), Some(UINT8_ARRAY));
+        assert_eq!(typed_array_of::<f32>(), Some(FLOAT32_ARRAY));
+        assert_eq!(typed_array_of::<u64>(), Some(BIGUINT64_ARRAY));
+        assert_eq!(typed_array_of::<usize>(), None);
+        assert_eq!(typed_array_of::<&f64>(), None);
+        assert_eq!(typed_array_of::<AsNumber<i64>>(), None);
+        assert_eq!(typed_array_of::<JSObject>(), None);
+    }
+
+    #[test]
+    fn bool_from_value_follows_truthiness() {
+        assert!(bool::from_js(&JSObject::from(true)));
+        assert!(!bool::from_js(&JSObject::from(false)));
+        assert!(bool::from_js(&JSObject::from(-0.5)));
+        assert!(!bool::from_js(&JSObject::from(0.0)));
+        assert!(!bool::from_js(&JSObject::from(-0.0)));
+        assert!(!bool::from_js(&JSObject::from(f64::NAN)));
+    }
+
+    #[test]
+    fn try_from_js_checks_kind() {
+        let error = String::try_from_js(&JSObject::from(1)).unwrap_err();
+        assert_eq!(error.name, "TypeError");
+        assert_eq!(error.message, "expected a string, found a number");
+
+        assert_eq!(i32::try_from_js(&JSObject::from(true)).unwrap_err().message,
+                   "expected a number, found a boolean");
+        assert_eq!(bool::try_from_js(&JSObject::from(1)).unwrap_err().message,
+                   "expected a boolean, found a number");
+        assert_eq!(f64::try_from_js(&JSObject::from(2.5)), Ok(2.5));
+        assert_eq!(u8::try_from_js(&JSObject::from(7)), Ok(7));
+        assert_eq!(i64::try_from_js(&JSObject::from(-3)), Ok(-3));
+        assert_eq!(AsNumber::<u64>::try_from_js(&JSObject::from(4)), Ok(AsNumber(4)));
+        assert_eq!(bool::try_from_js(&JSObject::from(false)), Ok(false));
+        assert_eq!(<()>::try_from_js(&JSObject::from(1)), Ok(()));
+    }
+
+    #[test]
+    fn try_from_js_checks_kind_of_optional_values() {
+        assert_eq!(Option::<f64>::try_from_js(&JSObject::from(1.5)), Ok(Some(1.5)));
+        assert_eq!(Nullish::<bool>::try_from_js(&JSObject::from(true)), Ok(Nullish::Value(true)));
+        assert_eq!(Option::<String>::try_from_js(&JSObject::from(1)).unwrap_err().message,
+                   "expected a string, found a number");
+    }
+
+    #[test]
+    fn bigint_words_are_least_significant_first() {
+        assert_eq!(bits_from_words(&[0, 0, 0, 0]), 0);
+        assert_eq!(bits_from_words(&[5, 0, 0, 0]), 5);
+        assert_eq!(bits_from_words(&[0x89AB_CDEF, 0x0123_4567, 0, 0]), 0x0123_4567_89AB_CDEF);
+        assert_eq!(bits_from_words(&[u32::MAX; 4]) as i128, -1);
+        assert_eq!(bits_from_words(&[u32::MAX, u32::MAX, 0, 0]) as i64, -1);
+    }
+
+    #[test]
+    fn element_errors_tell_the_index() {
+        let error = String::try_from_js(&JSObject::from(1)).map_err(|error| element_error(2, error)).unwrap_err();
+        assert_eq!(error.name, "TypeError");
+        assert_eq!(error.message, "element 2: expected a string, found a number");
+
+        // A `Vec<Vec<String>>` adds the index of the outer element in front.
+        assert_eq!(element_error(0, error).message, "element 0: element 2: expected a string, found a number");
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+
+/// A JavaScript exception caught by one of the `js_try` macros.
+///
+/// JavaScript allows any value to be thrown. When the thrown value is not an
+/// `Error` object, `name` and `stack` are empty and `message` holds the thrown
+/// value converted to a string.
+///
+/// # See also
+///
+/// [`js_try!`], [`js_try_int!`], [`js_try_double!`], [`js_try_string!`] and [`js_try_obj!`].
+///
+/// [`js_try!`]:        macro.js_try.html
+/// [`js_try_int!`]:    macro.js_try_int.html
+/// [`js_try_double!`]: macro.js_try_double.html
+/// [`js_try_string!`]: macro.js_try_string.html
+/// [`js_try_obj!`]:    macro.js_try_obj.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JSError {
+    /// The `name` property of the thrown value, e.g. `TypeError`.
+    pub name: String,
+    /// The `message` property of the thrown value.
+    pub message: String,
+    /// The `stack` property of the thrown value, if the JavaScript engine provides one.
+    pub stack: String,
+}
+
+impl JSError {
+    /// Takes the exception caught by `HELPERJS.tryCall`, if there is one.
+    ///
+    /// **Should not be used directly.** It is only public so that it can be used by the `js_try` macros.
+    #[doc(hidden)]
+    pub fn take() -> Option<JSError> {
+        if js_int!("return HELPERJS.HAS_ERROR ? 1 : 0;") == 0 {
+            return None;
+        }
+
+        let error = js_obj!("return HELPERJS.takeError();");
+        let name = js_double!("return HELPERJS.copyStringToHeap($0.name);", &error);
+        let message = js_double!("return HELPERJS.copyStringToHeap($0.message);", &error);
+        let stack = js_double!("return HELPERJS.copyStringToHeap($0.stack);", &error);
+
+        Some(JSError {
+            name: crate::string_from_js(name),
+            message: crate::string_from_js(message),
+            stack: crate::string_from_js(stack),
+        })
+    }
+}
+
+impl fmt::Display for JSError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.name, self.message)
+        }
+    }
+}
+
+impl std::error::Error for JSError {}
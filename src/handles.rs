@@ -0,0 +1,208 @@
+/*!
+Diagnostics for the [`HELPERJS`] object table.
+
+Every [`JSObject`] that refers to a JavaScript object holds a handle to an entry of the object table.
+A steadily growing table usually means `JSObject`s are being kept alive longer than intended, for
+example in a cache or a leaked `Box`. [`stats`] shows how the table is used and the optional leak
+tracker shows where the live handles were created:
+
+```ignore
+emscripten_helper_rs::handles::track_leaks(true);
+// ... run the code under suspicion ...
+for leak in emscripten_helper_rs::handles::report_leaks() {
+    println!("{}", leak);
+}
+```
+
+[`HELPERJS`]:    ../index.html#emscripten-helper-global
+[`JSObject`]:    ../struct.JSObject.html
+[`stats`]:       fn.stats.html
+*/
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::Location;
+
+thread_local! {
+    // Origin of every live handle, if leak tracking is enabled.
+    static ORIGINS: RefCell<Option<HashMap<u32, &'static Location<'static>>>> = const { RefCell::new(None) };
+}
+
+/// Usage statistics of the [`HELPERJS`] object table, as returned by [`stats`].
+///
+/// [`HELPERJS`]: ../index.html#emscripten-helper-global
+/// [`stats`]:    fn.stats.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandleStats {
+    /// Number of objects currently stored in the table.
+    pub live: u32,
+    /// Highest number of objects stored in the table at the same time.
+    pub peak: u32,
+    /// Number of released slots waiting to be reused.
+    pub free: u32,
+    /// Number of objects stored in the table since [`init`](../fn.init.html).
+    pub total_allocations: u64,
+}
+
+/// Returns usage statistics of the [`HELPERJS`] object table.
+///
+/// [`HELPERJS`]: ../index.html#emscripten-helper-global
+pub fn stats() -> HandleStats {
+    HandleStats {
+        live: js_int!("return HELPERJS.liveCount();") as u32,
+        peak: js_int!("return HELPERJS.PEAK_LIVE;") as u32,
+        free: js_int!("return HELPERJS.EMPTY_SLOTS.length;") as u32,
+        total_allocations: js_double!("return HELPERJS.TOTAL_STORED;") as u64,
+    }
+}
+
+/// Enables or disables the leak tracker.
+///
+/// While enabled, the Rust source location that created each handle is recorded, so that
+/// [`report_leaks`] can tell where the live handles come from. Only handles created while the
+/// tracker is enabled are recorded. Disabling the tracker forgets all recorded locations.
+///
+/// [`report_leaks`]: fn.report_leaks.html
+pub fn track_leaks(enable: bool) {
+    ORIGINS.with(|origins| {
+        let mut origins = origins.borrow_mut();
+        if enable != origins.is_some() {
+            *origins = if enable { Some(HashMap::new()) } else { None };
+        }
+    });
+}
+
+/// A live handle recorded by the leak tracker, as returned by [`report_leaks`].
+///
+/// [`report_leaks`]: fn.report_leaks.html
+#[derive(Debug, Clone)]
+pub struct LeakReport {
+    /// The handle of the object in the [`HELPERJS`](../index.html#emscripten-helper-global) object table.
+    pub handle: u32,
+    /// The Rust source location that created the handle.
+    pub origin: &'static Location<'static>,
+    /// A short description of the JavaScript object, such as its constructor name.
+    pub description: String,
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "handle {:#010x} created at {}: {}", self.handle, self.origin, self.description)
+    }
+}
+
+/// Lists the handles recorded by the leak tracker that are still alive, ordered by slot.
+///
+/// Returns an empty list if the leak tracker is not enabled. See [`track_leaks`].
+///
+/// [`track_leaks`]: fn.track_leaks.html
+pub fn report_leaks() -> Vec<LeakReport> {
+    live_origins().into_iter()
+        .map(|(handle, origin)| {
+            let description = js_obj!("return HELPERJS.describe($0);", handle as f64);
+            LeakReport {
+                handle,
+                origin,
+                description: String::from(description),
+            }
+        })
+        .collect()
+}
+
+// The recorded handles that are still alive with their origins, ordered by slot.
+fn live_origins() -> Vec<(u32, &'static Location<'static>)> {
+    let mut live: Vec<(u32, &'static Location<'static>)> = ORIGINS.with(|origins| {
+        match *origins.borrow() {
+            Some(ref origins) => origins.iter().map(|(&handle, &origin)| (handle, origin)).collect(),
+            None => Vec::new(),
+        }
+    });
+    live.sort_by_key(|&(handle, _)| slot(handle));
+    live
+}
+
+// Number of low bits of a handle holding its index in the object table. The high bits hold the
+// generation of the slot. Keep in sync with `helper.js` and `include/emscripten_helper.h`.
+const SLOT_BITS: u32 = 24;
+
+// The index in the object table of a handle, without its generation.
+fn slot(handle: u32) -> u32 {
+    handle & ((1 << SLOT_BITS) - 1)
+}
+
+// Called whenever a `JSObject` takes ownership of a new handle.
+pub(crate) fn record(handle: u32, origin: &'static Location<'static>) {
+    ORIGINS.with(|origins| {
+        if let Some(ref mut origins) = *origins.borrow_mut() {
+            origins.insert(handle, origin);
+        }
+    });
+}
+
+// Called whenever a handle is released.
+pub(crate) fn forget(handle: u32) {
+    ORIGINS.with(|origins| {
+        if let Some(ref mut origins) = *origins.borrow_mut() {
+            origins.remove(&handle);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JSObject;
+
+    #[test]
+    fn slot_ignores_generation() {
+        assert_eq!(slot(0x0000_0005), 5);
+        assert_eq!(slot(0x0300_0005), 5);
+        assert_eq!(slot(0xFFFF_FFFF), 0xFF_FFFF);
+    }
+
+    #[test]
+    fn tracks_live_handles_by_slot() {
+        let origin = Location::caller();
+        record(1, origin);
+        assert!(live_origins().is_empty(), "nothing is recorded while tracking is disabled");
+
+        track_leaks(true);
+        record(0x0100_0003, origin);
+        record(0x0200_0001, origin);
+        record(0x0000_0002, origin);
+        forget(0x0000_0002);
+        let handles: Vec<u32> = live_origins().into_iter().map(|(handle, _)| handle).collect();
+        assert_eq!(handles, [0x0200_0001, 0x0100_0003]);
+
+        track_leaks(false);
+        assert!(live_origins().is_empty());
+    }
+
+    #[test]
+    fn leaked_handles_are_not_tracked() {
+        track_leaks(true);
+        let kept = unsafe { JSObject::from_raw(0x0000_0007) }.into_raw();
+        let leaked = unsafe { JSObject::from_raw(0x0000_0008) }.leak();
+        let handles: Vec<u32> = live_origins().into_iter().map(|(handle, _)| handle).collect();
+        assert_eq!(handles, [kept]);
+        assert_eq!(leaked, 0x0000_0008);
+        track_leaks(false);
+    }
+
+    #[test]
+    fn c_header_matches_handle_layout() {
+        let header = include_str!("../include/emscripten_helper.h");
+        assert!(header.contains(&format!("#define EMH_SLOT_BITS {}\n", SLOT_BITS)));
+        assert!(header.contains(&format!("#define EMH_SLOT_MASK {:#X}u\n", (1u32 << SLOT_BITS) - 1)));
+
+        let helper = include_str!("helper.js");
+        assert!(helper.contains(&format!("  SLOT_BITS: {},\n", SLOT_BITS)));
+
+        // Every HELPERJS function used by the header must exist.
+        for used in header.split("HELPERJS.").skip(1) {
+            let name: String = used.chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+            assert!(helper.contains(&format!("  {}: function(", name)), "HELPERJS.{} is not defined", name);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::marker::PhantomData;
+
+use crate::{scope, IntoAsmConstArg, JSObject, TypedArrayElement};
+
+/// A typed array over a Rust slice, shared with JavaScript without copying it.
+///
+/// Passing a `&HeapView` to the [`js!`] family of macros gives the snippet a typed array, such as a
+/// `Float32Array` for a slice of `f32`, that is a `subarray` of the emscripten memory. Converting a slice
+/// with [`RefIntoJs`] copies it instead.
+///
+/// Growing the emscripten memory (with `ALLOW_MEMORY_GROWTH`) detaches the typed arrays over the previous
+/// memory, which happens whenever Rust or JavaScript code allocates. The view hands out a typed array over
+/// the current memory every time it is passed to a snippet, so a view can be kept across allocations. Typed
+/// arrays kept by JavaScript code are not updated, and should not be used after the snippet returns.
+///
+/// ```ignore
+/// let samples: Vec<f32> = decode_audio();
+/// let view = HeapView::new(&samples);
+/// js!("buffer.copyToChannel($0, 0);", &view);
+/// ```
+///
+/// The view borrows the slice for its whole lifetime. A view created with [`HeapView::new`] must not be
+/// modified by JavaScript code, while a view created with [`HeapView::new_mut`] can be. Once the view is
+/// dropped, it is emptied, so that JavaScript code holding on to it cannot access the memory anymore.
+///
+/// A view does not belong to the [`scope`] it is created in: it stays valid until it is dropped, like the
+/// borrow of the slice.
+///
+/// [`js!`]:               macro.js.html
+/// [`RefIntoJs`]:         trait.RefIntoJs.html
+/// [`HeapView::new`]:     struct.HeapView.html#method.new
+/// [`HeapView::new_mut`]: struct.HeapView.html#method.new_mut
+/// [`scope`]:             fn.scope.html
+#[derive(Debug)]
+pub struct HeapView<'a, T: TypedArrayElement> {
+    // A HELPERJS.HeapView, which is replaced by its typed array when passed to a snippet.
+    view: JSObject,
+    marker: PhantomData<&'a [T]>,
+}
+
+impl<'a, T: TypedArrayElement> HeapView<'a, T> {
+    /// Creates a view over `slice`, which JavaScript code must not modify.
+    #[track_caller]
+    pub fn new(slice: &'a [T]) -> Self {
+        HeapView::from_raw_parts(slice.as_ptr(), slice.len())
+    }
+
+    /// Creates a view over `slice`, which JavaScript code can modify.
+    #[track_caller]
+    pub fn new_mut(slice: &'a mut [T]) -> Self {
+        HeapView::from_raw_parts(slice.as_ptr(), slice.len())
+    }
+
+    #[track_caller]
+    fn from_raw_parts(ptr: *const T, len: usize) -> Self {
+        let view = js_obj!("return new HELPERJS.HeapView($0, $1, $2);",
+                           T::TYPED_ARRAY, ptr, len);
+        // The view is emptied when dropped, which needs its handle even after an enclosing scope exits.
+        scope::release_on_drop(&view);
+        HeapView {
+            view,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns a copy of the slice as a new typed array.
+    ///
+    /// Unlike the view, the copy does not borrow the slice: it stays valid when the memory grows and after
+    /// the view is dropped. Pass the view itself to snippets to avoid the copy.
+    #[track_caller]
+    pub fn to_array(&self) -> JSObject {
+        js_obj!("return $0.slice();", &self.view)
+    }
+}
+
+impl<'a, T: TypedArrayElement> Drop for HeapView<'a, T> {
+    fn drop(&mut self) {
+        // Passed as a number, since passing the view as an object would give its typed array.
+        js!("HELPERJS.releaseView(HELPERJS.loadObject($0));", self.view.value);
+    }
+}
+
+impl<'a, 'b, T: TypedArrayElement> IntoAsmConstArg for &'b HeapView<'a, T> {
+    type Arg = &'b JSObject;
+    fn into_asm_const_arg(self) -> &'b JSObject {
+        (&self.view).into_asm_const_arg()
+    }
+}
@@ -3,24 +3,363 @@
 HELPERJS = {
   STORAGE: [], // A place to store JS objects so they can be referenced by their index from Rust.
   EMPTY_SLOTS: [], // List of slots in STORAGE that have been freed and can be reused.
+  GENERATIONS: [], // Generation of each slot in STORAGE, bumped every time the slot is released.
+  PEAK_LIVE: 0, // Highest number of live objects in STORAGE so far.
+  TOTAL_STORED: 0, // Number of calls to storeObject so far.
+  HAS_ERROR: false, // Whether tryCall caught an exception that has not been taken yet.
+  LAST_ERROR: null, // The exception caught by tryCall.
+  modules: {}, // Modules declared with js_module! in Rust, by name.
 
-  // Store an object in this.STORAGE and return the slot number.
+  // Handles given to Rust are 32-bit unsigned integers. The low 24 bits
+  // are the slot number in this.STORAGE and the high 8 bits are the
+  // generation of the slot when the handle was created. Generations run
+  // from 1 to 255 and wrap around, so a handle is never 0.
+  SLOT_BITS: 24,
+  SLOT_MASK: 0xFFFFFF,
+
+  // Store an object in this.STORAGE and return its handle.
   storeObject: function(obj) {
       var idx = this.EMPTY_SLOTS.pop();
-      if (undefined == idx) { idx = this.STORAGE.length };
+      if (undefined == idx) {
+          idx = this.STORAGE.length;
+          if (idx > this.SLOT_MASK) { throw new Error("HELPERJS.storeObject: object table is full"); }
+          this.GENERATIONS[idx] = 1;
+      };
       this.STORAGE[idx] = obj;
-      return idx;
+      this.TOTAL_STORED++;
+      this.PEAK_LIVE = Math.max(this.PEAK_LIVE, this.liveCount());
+      return ((this.GENERATIONS[idx] << this.SLOT_BITS) | idx) >>> 0;
+  },
+
+  // Release count handles stored as uint32 at ptr in Emscripten memory.
+  // Returns the number of handles that were actually released.
+  releaseMany: function(ptr, count) {
+      var heap = this.heap(this.UINT32);
+      var released = 0;
+      for (var idx = 0; idx < count; idx++) {
+          released += this.releaseObject(heap[ptr / 4 + idx]);
+      }
+      return released;
+  },
+
+  // Create a WeakRef to obj.
+  makeWeak: function(obj) {
+      return new WeakRef(obj);
+  },
+
+  // Call the Rust callback at ptr once obj has been garbage collected.
+  // The FinalizationRegistry is created on first use, so that pages that
+  // never register a finalizer do not require browser support for it.
+  registerFinalizer: function(obj, ptr) {
+      if (this.FINALIZERS === undefined) {
+          this.FINALIZERS = new FinalizationRegistry(function(ptr) {
+              Module._emscripten_helper_run_finalizer(ptr);
+          });
+      }
+      this.FINALIZERS.register(obj, ptr);
   },
 
-  // Load an object from this.STORAGE. 
-  loadObject: function(idx) {
-      return this.STORAGE[idx];
+  // Number of slots in this.STORAGE that currently hold an object.
+  liveCount: function() {
+      return this.STORAGE.length - this.EMPTY_SLOTS.length;
   },
 
-  // Release a slot in this.STORAGE.
-  releaseObject: function(idx) {
+  // Describe the object behind handle in a short string for diagnostics.
+  describe: function(handle) {
+      if (!this.isValidHandle(handle)) { return "<released>"; }
+      var obj = this.STORAGE[handle & this.SLOT_MASK];
+      var description;
+      try {
+          if (obj !== null && typeof obj === "object") {
+              var name = obj.constructor && obj.constructor.name ? obj.constructor.name : "Object";
+              description = Array.isArray(obj) ? name + "(" + obj.length + ")" : name;
+          } else if (typeof obj === "function") {
+              description = "function " + (obj.name || "<anonymous>");
+          } else if (typeof obj === "string") {
+              description = JSON.stringify(obj);
+          } else {
+              description = String(obj);
+          }
+      } catch (e) {
+          description = typeof obj;
+      }
+      return description.length > 80 ? description.substring(0, 77) + "..." : description;
+  },
+
+  // Whether handle refers to a slot that has not been released since
+  // the handle was created.
+  isValidHandle: function(handle) {
+      var idx = handle & this.SLOT_MASK;
+      return (handle >>> this.SLOT_BITS) === this.GENERATIONS[idx] && idx in this.STORAGE;
+  },
+
+  // Return the slot number of handle, throwing if the handle is stale.
+  slotOf: function(handle, caller) {
+      if (!this.isValidHandle(handle)) {
+          throw new Error("HELPERJS." + caller + ": stale or invalid handle " + handle +
+                          " (slot " + (handle & this.SLOT_MASK) + ", generation " + (handle >>> this.SLOT_BITS) + ")");
+      }
+      return handle & this.SLOT_MASK;
+  },
+
+  // Load an object from this.STORAGE.
+  loadObject: function(handle) {
+      return this.STORAGE[this.slotOf(handle, "loadObject")];
+  },
+
+  // Release a slot in this.STORAGE. Returns 0 instead of throwing if the
+  // handle is stale, since this is called from Drop in Rust.
+  releaseObject: function(handle) {
+      if (!this.isValidHandle(handle)) { return 0; }
+      var idx = handle & this.SLOT_MASK;
+      var generation = this.GENERATIONS[idx];
+      this.GENERATIONS[idx] = generation === 255 ? 1 : generation + 1;
       delete this.STORAGE[idx];
       this.EMPTY_SLOTS.push(idx);
+      return 1;
+  },
+
+  // Call func with the arguments of a snippet, after turning them into
+  // Javascript values. kinds packs 2 bits per argument: 0 for a plain
+  // value, 1 for a handle, 2 for a boolean and 3 for an unsigned 64-bit
+  // BigInt passed as signed. The codes match the KIND_ constants on the
+  // Rust side, so keep both in sync. A handle to a HeapView is replaced
+  // by its typed array.
+  unwrapCall: function(kinds, func, args) {
+      for (var idx = 0; idx < args.length; idx++) {
+          var kind = (kinds >>> (2 * idx)) & 3;
+          if (kind === 1) {
+              args[idx] = this.loadObject(args[idx]);
+              if (args[idx] instanceof this.HeapView) { args[idx] = this.viewOf(args[idx]); }
+          } else if (kind === 2) {
+              args[idx] = args[idx] !== 0;
+          } else if (kind === 3) {
+              args[idx] = BigInt.asUintN(64, args[idx]);
+          }
+      }
+      return func.apply(null, args);
+  },
+
+  // Names of the typed array constructors, by the codes used on the Rust
+  // side for the element types of slices, so keep both in sync. They
+  // are looked up on use, for engines without BigInt64Array.
+  TYPED_ARRAYS: ["Int8Array", "Uint8Array", "Int16Array", "Uint16Array", "Int32Array",
+                 "Uint32Array", "Float32Array", "Float64Array", "BigInt64Array", "BigUint64Array"],
+  UINT8: 1,
+  UINT16: 3,
+  UINT32: 5,
+  FLOAT64: 7,
+  HEAPS: [], // Views of the whole Emscripten memory, by typed array type.
+
+  // The ArrayBuffer currently backing Emscripten memory. Growing the memory
+  // replaces it and detaches the previous one, so it must not be cached.
+  memoryBuffer: function() {
+      if (typeof wasmMemory !== "undefined") { return wasmMemory.buffer; }
+      if (Module.wasmMemory) { return Module.wasmMemory.buffer; }
+      return Module.HEAPU8.buffer;
+  },
+
+  // A view of the given typed array type over the whole Emscripten memory,
+  // rebuilt if the memory has grown since it was created. Call it again
+  // after anything that may allocate, such as Module._malloc.
+  heap: function(type) {
+      var buffer = this.memoryBuffer();
+      var view = this.HEAPS[type];
+      if (view === undefined || view.buffer !== buffer) {
+          view = this.HEAPS[type] = new globalThis[this.TYPED_ARRAYS[type]](buffer);
+      }
+      return view;
+  },
+
+  // A view over length elements of the given typed array type at ptr in
+  // Emscripten memory, created by HeapView on the Rust side. Snippets get
+  // the typed array of viewOf instead of the HeapView itself.
+  HeapView: function(type, ptr, length) {
+      this.type = type;
+      this.ptr = ptr;
+      this.length = length;
+      this.array = null;
+  },
+
+  // The typed array of view, rebuilt if the memory has grown since it was
+  // created.
+  viewOf: function(view) {
+      var buffer = this.memoryBuffer();
+      if (view.array === null || view.array.buffer !== buffer) {
+          view.array = new globalThis[this.TYPED_ARRAYS[view.type]](buffer, view.ptr, view.length);
+      }
+      return view.array;
+  },
+
+  // Make view empty once the Rust slice it points at is no longer borrowed.
+  // Typed arrays obtained from it before still point at the memory.
+  releaseView: function(view) {
+      view.ptr = 0;
+      view.length = 0;
+      view.array = null;
+  },
+
+  // Build an array from count doubles at values_ptr in Emscripten memory.
+  // The byte at the same index at kinds_ptr tells what each value is, with
+  // the codes of unwrapCall: handles are replaced by the objects they refer
+  // to and booleans by true or false.
+  makeArray: function(values_ptr, kinds_ptr, count) {
+      var values = this.heap(this.FLOAT64);
+      var kinds = this.heap(this.UINT8);
+      var array = new Array(count);
+      for (var idx = 0; idx < count; idx++) {
+          var value = values[values_ptr / 8 + idx];
+          var kind = kinds[kinds_ptr + idx];
+          array[idx] = kind === 1 ? this.loadObject(value) : kind === 2 ? value !== 0 : value;
+      }
+      return array;
+  },
+
+  // Copy length elements of the given typed array type at ptr in
+  // Emscripten memory into a new typed array.
+  copyArrayFromHeap: function(type, ptr, length) {
+      var ctor = globalThis[this.TYPED_ARRAYS[type]];
+      var bytes = this.heap(this.UINT8).slice(ptr, ptr + length * ctor.BYTES_PER_ELEMENT);
+      return new ctor(bytes.buffer);
+  },
+
+  // Return the length of value if it is a typed array that can be copied
+  // into a typed array of the given type, or -1 otherwise. BigInt and
+  // number typed arrays cannot be copied into each other.
+  typedArrayLength: function(value, type) {
+      if (!ArrayBuffer.isView(value) || value instanceof DataView) { return -1; }
+      var name = Object.prototype.toString.call(value);
+      var bigint = this.TYPED_ARRAYS[type].indexOf("Big") === 0;
+      return (name.indexOf("[object Big") === 0) === bigint ? value.length : -1;
+  },
+
+  // Copy up to length elements of array into Emscripten memory at ptr,
+  // converting them to the given typed array type. Returns the number of
+  // elements copied.
+  copyArrayToHeap: function(type, array, ptr, length) {
+      var count = Math.min(length, array.length);
+      var ctor = globalThis[this.TYPED_ARRAYS[type]];
+      new ctor(this.memoryBuffer(), ptr, count).set(array.subarray(0, count));
+      return count;
+  },
+
+  // Return value as an array: arrays as is, and the elements of any other
+  // iterable such as a Set, a NodeList or a generator. Throws a TypeError
+  // for values that are not iterable.
+  toArray: function(value) {
+      if (Array.isArray(value)) { return value; }
+      if (value === null || value === undefined || typeof value[Symbol.iterator] !== "function") {
+          throw new TypeError("HELPERJS.toArray: expected an array or an iterable, found " +
+                              (value === null ? "null" : typeof value));
+      }
+      return Array.from(value);
+  },
+
+  // Call func with this set to this_arg and the elements of
+  // the array args as arguments.
+  invoke: function(func, this_arg, args) {
+      if (typeof func !== "function") {
+          throw new TypeError("HELPERJS.invoke: " + String(func) + " is not a function");
+      }
+      return func.apply(this_arg, args);
+  },
+
+  // Compare a and b. The codes for op match the Comparison enum on
+  // the Rust side, so keep both in sync.
+  compare: function(a, b, op) {
+      switch (op) {
+          case 0: return a === b;
+          case 1: return a == b;
+          case 2: return Object.is(a, b);
+          default: return a instanceof b;
+      }
+  },
+
+  // Look up a dotted path such as "a.b.c" starting from obj. Returns
+  // undefined as soon as a segment cannot be resolved.
+  getPath: function(obj, path) {
+      var segments = path.split(".");
+      for (var idx = 0; idx < segments.length; idx++) {
+          if (obj === null || obj === undefined) { return undefined; }
+          obj = obj[segments[idx]];
+      }
+      return obj;
+  },
+
+  // Resolve a dotted path such as "document.body" from the global
+  // object, throwing a ReferenceError if a segment is missing. The
+  // Emscripten Module object is found even if it is not a global.
+  lookup: function(path) {
+      var segments = path.split(".");
+      var obj = globalThis;
+      for (var idx = 0; idx < segments.length; idx++) {
+          var segment = segments[idx];
+          if (idx === 0 && segment === "Module" && !("Module" in globalThis)) {
+              obj = Module;
+              continue;
+          }
+          if (obj === null || obj === undefined || !(segment in Object(obj))) {
+              throw new ReferenceError(segments.slice(0, idx + 1).join(".") + " is not defined");
+          }
+          obj = obj[segment];
+      }
+      return obj;
+  },
+
+  // Classify obj. The codes match the variants of JSKind on the
+  // Rust side, so keep both in sync.
+  typeOf: function(obj) {
+      if (obj === null) { return 1; }
+      switch (typeof obj) {
+          case "undefined": return 0;
+          case "boolean": return 2;
+          case "number": return 3;
+          case "string": return 4;
+          case "bigint": return 5;
+          case "symbol": return 6;
+          case "function": return 8;
+          default: return Array.isArray(obj) ? 7 : 9;
+      }
+  },
+
+  // Call func and return its result. If it throws, the exception is kept
+  // until Rust collects it with takeError() and 0 is returned instead.
+  tryCall: function(func) {
+      try {
+          return func();
+      } catch (e) {
+          this.HAS_ERROR = true;
+          this.LAST_ERROR = e;
+          return 0;
+      }
+  },
+
+  // Take the exception caught by tryCall and describe it as an object
+  // with string name, message and stack properties. Anything can be
+  // thrown in Javascript, so non-Error values are described as best we can.
+  takeError: function() {
+      var e = this.LAST_ERROR;
+      var is_object = e !== null && (typeof e === "object" || typeof e === "function");
+      this.HAS_ERROR = false;
+      this.LAST_ERROR = null;
+      return {
+          name: is_object && e.name !== undefined ? String(e.name) : "",
+          message: is_object && e.message !== undefined ? String(e.message) : String(e),
+          stack: is_object && e.stack !== undefined ? String(e.stack) : ""
+      };
+  },
+
+  // Write the BigInt value as count little-endian 32-bit words at ptr in
+  // Emscripten memory, wrapping around like an integer cast in Rust.
+  // Returns 0 without writing anything if value is not a BigInt.
+  storeBigInt: function(value, ptr, count) {
+      if (typeof value !== "bigint") { return 0; }
+      var heap = this.heap(this.UINT32);
+      for (var idx = 0; idx < count; idx++) {
+          heap[ptr / 4 + idx] = Number(BigInt.asUintN(32, value));
+          value >>= 32n;
+      }
+      return 1;
   },
 
   // Copy a Javascript string to Emscripten memory.
@@ -30,22 +369,24 @@ HELPERJS = {
   copyStringToHeap: function(string) {
       var char_count = string.length;
       var buf = Module._malloc(4 + char_count*2);
-      Module.HEAPU32[buf / 4] = char_count;
+      // _malloc may have grown the memory, so the views are taken after it.
+      this.heap(this.UINT32)[buf / 4] = char_count;
+      var heap = this.heap(this.UINT16);
       for (var idx=0; idx < char_count; idx++) {
-          Module.HEAPU16[2 + buf/2 + idx] = string.charCodeAt(idx);
+          heap[2 + buf/2 + idx] = string.charCodeAt(idx);
       }
       return buf;
   },
 
   // Copy a UTF-16 encoded string from Emscripten heap
-  // into a Javascript string.
+  // into a Javascript string. The memory is still owned by Rust.
   copyStringFromHeap: function(ptr, size) {
       var string = "";
+      var heap = this.heap(this.UINT16);
       var offset = ptr / 2;
       for (var idx = 0; idx < size; idx++) {
-          string = string.concat(String.fromCharCode(Module.HEAPU16[offset+idx]));
+          string = string.concat(String.fromCharCode(heap[offset+idx]));
       }
-      Module._free(ptr);
       return string;
   }
 };
\ No newline at end of file
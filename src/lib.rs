@@ -8,6 +8,53 @@ It currently requires using the rust nightly channel and either the `asmjs-unkno
 or `wasm-unknown-emscripten` targets. See the README for any help setting up a development
 environment.
 
+# Declaring JavaScript functions
+
+The [`js_function`] attribute turns a function declaration into a Rust function implemented in
+JavaScript, with the arguments and the return value converted according to the declared types:
+
+```ignore
+#[js_function("return a + b;")]
+fn add(a: f64, b: f64) -> f64;
+```
+
+# JavaScript modules
+
+Helper functions shared by several snippets can be declared once with [`js_module!`]. They are
+installed under `HELPERJS.modules` by [`init`], and can be called through generated Rust wrappers, or from
+snippets:
+
+```ignore
+js_module!(pub geometry {
+    fn length(x, y) { return Math.sqrt(x * x + y * y); }
+});
+
+let len = f64::from(geometry::length(3, 4));
+js!("console.log(HELPERJS.modules.geometry.length(6, 8));");
+```
+
+# Named arguments
+
+Instead of referring to their arguments as `$0`, `$1`, ..., all the `js!` macros accept named
+arguments that are interpolated into the snippet like in `format!`:
+
+```ignore
+js!("{el}.textContent = {text};", el = node, text = "hi");
+```
+
+The names are replaced with positional parameters at compile time. Naming an argument that the
+snippet does not use, or using a name that is not an argument, is a compile error. As in `format!`,
+literal braces must be doubled, so `{{` and `}}` stand for `{` and `}`. Named and positional
+arguments cannot be mixed, and a snippet with named arguments must be a single string literal.
+
+# Snippet checking
+
+The JavaScript snippets given to the macros of this crate are checked when the Rust code is compiled.
+Unterminated strings, template literals, comments and regular expressions, as well as unbalanced
+brackets, are reported as compile errors pointing at the snippet. This is a lexical check, not a full
+JavaScript parser: other syntax errors are still only reported by the browser. Snippets that are not
+made of string literals, such as `include_str!`, are not checked.
+
 # Javascript helpers
 
 `HELPERJS` provides a number of helper JavaScript functions to store and convert JavaScript objects to be
@@ -20,21 +67,121 @@ A global JavaScript object named `HELPERJS` is created by [`init`]. It contains
 functions as well as an object table to keep the JavaScript objects that are held by Rust code. See
 [`JSObject`] for more details.
 
-## `HELPERJS.loadObject(index)`
+## Handles
+
+Objects in the object table are referred to by handles. A handle is a 32-bit unsigned integer whose low
+24 bits are the index of the slot in the object table and whose high 8 bits are the generation of that
+slot. The generation is bumped every time the slot is released, so a handle that outlives its object
+(or is released twice) is detected instead of silently referring to whatever object reuses the slot.
+Generations wrap around after 255 reuses of a slot, so detection is best-effort.
+
+## `HELPERJS.loadObject(handle)`
 
-Loads a JavaScript object from the object table and returns it.
+Loads a JavaScript object from the object table and returns it. Throws an `Error` if the handle is
+stale or invalid.
 
 ## `HELPERJS.storeObject(js_object)`
 
-Stores an object into the object table and returns the index. The result is commonly wrapped into
+Stores an object into the object table and returns its handle. The result is commonly wrapped into
 a [`JSObject`] by [`js_obj!`].
 
+## `HELPERJS.isValidHandle(handle)`
+
+Returns whether `handle` refers to an object that is still in the object table.
+
+## `HELPERJS.modules`
+
+Holds the modules declared with [`js_module!`], by name. Their functions can be called from any snippet,
+e.g. `HELPERJS.modules.geometry.length($0, $1)`.
+
 ## Private helper functions
 
-### `HELPERJS.releaseObject(index)`
+### `HELPERJS.releaseObject(handle)`
 
 Removes an object from the object table. For use in the `Drop` implementation of [`JSObject`] only.
-Releasing an object that is still refered to by a `JSObject` will cause problems.
+Returns `1` on success and `0` if the handle was stale or already released.
+
+### `HELPERJS.releaseMany(pointer, count)`
+
+Releases `count` handles stored as 32-bit unsigned integers at `pointer` in the Rust heap. Used by
+[`scope`] to release all temporaries of a scope in one call.
+
+### `HELPERJS.makeWeak(object)` and `HELPERJS.registerFinalizer(object, pointer)`
+
+Create a `WeakRef` to `object`, and arrange for the Rust callback at `pointer` to run once `object`
+has been garbage collected. Used by [`JSWeak`] and [`JSObject::register_finalizer`].
+
+### `HELPERJS.liveCount()` and `HELPERJS.describe(handle)`
+
+Return the number of objects in the object table and a short description of the object behind a
+handle. Used by the [`handles`] diagnostics.
+
+### `HELPERJS.unwrapCall(kinds, func, args)`
+
+Converts the elements of `args` into JavaScript values according to `kinds`, loading handles from the
+object table, turning booleans into `true` or `false` and unsigned 64-bit integers into positive `BigInt`s,
+and calls `func` with them. Every snippet run by the macros of this crate goes through it, see
+[`IntoAsmConstArg`].
+
+### `HELPERJS.storeBigInt(value, pointer, count)`
+
+Writes the `BigInt` `value` as `count` little-endian 32-bit words at `pointer` in the Rust heap, wrapping
+around if it does not fit, and returns `1`. Returns `0` if `value` is not a `BigInt`. Used to convert
+`BigInt`s into `i64`, `u64`, `i128` and `u128`, see [`AsNumber`].
+
+### `HELPERJS.makeArray(values, kinds, count)`
+
+Builds an array from `count` doubles at `values` in the Rust heap. The byte at the same index in `kinds` tells
+whether a value is a number, a handle replaced by the object it refers to, or a boolean. Used to convert `Vec`s
+and slices into arrays in a single call.
+
+### `HELPERJS.copyArrayFromHeap(type, pointer, length)`, `HELPERJS.typedArrayLength(value, type)` and `HELPERJS.copyArrayToHeap(type, array, pointer, length)`
+
+Copy `length` numbers at `pointer` in the Rust heap into a new typed array, and back. `type` is an index
+into `HELPERJS.TYPED_ARRAYS`, the names of the typed array constructors. Used to convert slices of numbers
+to and from typed arrays.
+
+### `HELPERJS.toArray(value)`
+
+Returns `value` if it is an array, or an array of its elements if it is any other iterable. Throws a
+`TypeError` otherwise. Used to convert JavaScript arrays and iterables into `Vec`s.
+
+### `HELPERJS.invoke(func, this_arg, args)`
+
+Calls `func` with `this` set to `this_arg` and the elements of the array `args` as arguments.
+Used by [`JSObject::call`] and [`JSObject::call_method`].
+
+### `HELPERJS.compare(a, b, op)`
+
+Compares `a` and `b` with `===`, `==`, `Object.is` or `instanceof`, depending on `op`. Used by
+[`JSObject::js_eq`] and the other comparison methods of [`JSObject`].
+
+### `HELPERJS.getPath(object, path)`
+
+Resolves a dotted path such as `"a.b.c"` starting from `object`, returning `undefined` if any
+segment is missing. Used by [`JSObject::get_path`].
+
+### `HELPERJS.lookup(path)`
+
+Resolves a dotted path such as `"document.body"` starting from the global object, throwing a
+`ReferenceError` if any segment is missing. A leading `Module` refers to the emscripten `Module`
+object even if it is not a global. Used by [`lookup`].
+
+### `HELPERJS.typeOf(value)`
+
+Returns a code describing the type of `value`. Used by
+[`JSObject::kind`].
+
+### `HELPERJS.tryCall(func)`
+
+Calls `func` and returns its result. If `func` throws, the exception is kept in `HELPERJS.LAST_ERROR`
+and `0` is returned instead. Used by [`js_try!`] and the other `js_try` macros around the whole call to
+`unwrapCall`, so that stale handles passed as arguments are caught too.
+
+### `HELPERJS.takeError()`
+
+Takes the exception caught by `tryCall` and returns an object with the `name`, `message` and `stack`
+of the thrown value as strings. Used to build a [`JSError`].
 
 ### `HELPERJS.copyStringToHeap(js_string)`
 
@@ -42,26 +189,57 @@ Copy a JavaScript string into the Rust heap and returns the address. The string
 unsigned integer containing the number of 16-bit code units (not bytes or characters!) in the buffer,
 followed by that number of 16-bit code units from the UTF-16 string.
 
-Used by [`js_string!`] and the implementation of `std::convert::From<JSObject> for String`.
+Used by [`js_string!`] and the implementation of [`FromJs`] for `String`.
+
+### `HELPERJS.copyStringFromHeap(pointer, length)`
 
-### `HELPERJS.copyStringFromHeap(pointer)`
+Convert `length` UTF-16 code units stored at `pointer` on the Rust heap into a JavaScript string. The
+memory is left to the caller.
 
-Convert a String stored on the Rust heap (as described in [`copyStringToHeap`]) into a JavaScript
-string. The memory is freed after conversion, so the pointer should be considered invalid by the caller.
+### `HELPERJS.heap(type)` and `HELPERJS.memoryBuffer()`
+
+Return a typed array over the whole emscripten memory, with `type` an index into `HELPERJS.TYPED_ARRAYS`,
+and the `ArrayBuffer` behind it. Growing the memory detaches the previous `ArrayBuffer`, so the helpers that
+access the Rust heap call these every time instead of keeping a view.
+
+### `HELPERJS.HeapView(type, pointer, length)`, `HELPERJS.viewOf(view)` and `HELPERJS.releaseView(view)`
+
+The JavaScript side of [`HeapView`]. `viewOf` returns the typed array of a view, rebuilt if the memory has
+grown, and is called by `unwrapCall` for every view passed to a snippet.
 
 [`init`]:     fn.init.html
+[`js_function`]: attr.js_function.html
+[`js_module!`]: macro.js_module.html
 [`js_obj!`]:  macro.js_obj.html
 [`js_int!`]:    macro.js_int.html
 [`js_double!`]: macro.js_double.html
 [`js_string!`]: macro.js_string.html
 [`js!`]:      macro.js.html
+[`js_try!`]:  macro.js_try.html
 [`JSObject`]: struct.JSObject.html
+[`JSError`]:  struct.JSError.html
+[`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
+[`AsNumber`]: struct.AsNumber.html
+[`HeapView`]: struct.HeapView.html
+[`FromJs`]:   trait.FromJs.html
+[`handles`]:  handles/index.html
+[`scope`]:    fn.scope.html
+[`lookup`]:   fn.lookup.html
+[`JSWeak`]:   struct.JSWeak.html
+[`JSObject::register_finalizer`]: struct.JSObject.html#method.register_finalizer
+[`JSObject::kind`]: struct.JSObject.html#method.kind
+[`JSObject::get_path`]: struct.JSObject.html#method.get_path
+[`JSObject::js_eq`]: struct.JSObject.html#method.js_eq
+[`JSObject::call`]: struct.JSObject.html#method.call
+[`JSObject::call_method`]: struct.JSObject.html#method.call_method
 [`copyStringToHeap`]: index.html#rsjscopystringtoheapjs_string
 */ 
 
-use std::ptr;
+use std::cell::Cell;
 use std::rc::Rc;
+use std::sync::{Mutex, PoisonError};
 
+#[allow(non_camel_case_types)]
 type em_callback_func = unsafe extern "C" fn(context: *mut std::os::raw::c_void);
 
 /// This module declares C functions provided by either emscripten or the C standard library.
@@ -115,7 +293,7 @@ pub mod emscripten {
         #[allow(dead_code)]
         pub fn emscripten_pause_main_loop();
         #[allow(dead_code)]
-        pub fn emscripten_set_main_loop(func: extern fn(), fps: std::os::raw::c_int, infinite: std::os::raw::c_int);
+        pub fn emscripten_set_main_loop(func: extern "C" fn(), fps: std::os::raw::c_int, infinite: std::os::raw::c_int);
         #[allow(dead_code)]
         pub fn emscripten_set_main_loop_arg(
             func: crate::em_callback_func,
@@ -129,10 +307,44 @@ pub mod emscripten {
     }
 }
 
-fn string_from_js(ptr: *mut u16) -> String {
+// Unit tests run on the host, where the emscripten runtime is missing. They only exercise code that never
+// reaches JavaScript, but the functions they call still need these symbols to link.
+#[cfg(all(test, not(target_os = "emscripten")))]
+mod host {
+    #[no_mangle]
+    extern "C" fn emscripten_asm_const_int() -> std::os::raw::c_int {
+        unreachable!("JavaScript is not available in host tests")
+    }
+
+    #[no_mangle]
+    extern "C" fn emscripten_asm_const_double() -> std::os::raw::c_double {
+        unreachable!("JavaScript is not available in host tests")
+    }
+
+    // Runs `script` with node and returns what it printed. Tests that check generated snippets against
+    // helper.js use it, and are skipped rather than failed on hosts where node is not installed.
+    pub(crate) fn run_node(script: &str) -> Option<String> {
+        match std::process::Command::new("node").arg("-e").arg(script).output() {
+            Ok(output) => {
+                assert!(output.status.success(), "node failed: {}", String::from_utf8_lossy(&output.stderr));
+                Some(String::from_utf8(output.stdout).expect("node printed invalid UTF-8"))
+            }
+            Err(ref error) if error.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("node is not installed, skipping");
+                None
+            }
+            Err(error) => panic!("cannot run node: {}", error),
+        }
+    }
+}
+
+// Takes the string copied by `HELPERJS.copyStringToHeap` to `address`. The address is returned by a `js_double!`
+// snippet, since pointers do not fit in the `c_int` returned by `js_int!` with `MEMORY64`.
+fn string_from_js(address: f64) -> String {
+    let ptr = address as usize as *mut u16;
     unsafe {
         let size : u32 = *(ptr as *const _ as *const u32);
-        let string_slice : &'static [u16] = std::slice::from_raw_parts(ptr, size as usize);
+        let string_slice : &'static [u16] = std::slice::from_raw_parts(ptr.offset(2), size as usize);
         let result = String::from_utf16_lossy(string_slice);
         emscripten::free(ptr as *mut _);
         result
@@ -152,49 +364,102 @@ pub fn js_eval(code: &'static [u8]) {
 ///
 /// **Should not be used directly.**
 ///
+/// The code snippet is a NUL-terminated constant, so every call site hands the same static address to
+/// emscripten and no allocation happens per call. Each argument is converted with [`IntoAsmConstArg`] and
+/// passed with its own signature character, see [`AsmConstArgs`]. This is why `$jscode` has to be a
+/// string literal (or a `concat!` of literals).
+///
+/// The snippet is wrapped in a function called through `HELPERJS.unwrapCall`, which receives the kinds of the
+/// arguments as the real `$0` and resolves handles and booleans, so that `$0`, `$1`, ... in `$jscode` are the
+/// JavaScript values of the arguments. At most 15 arguments can be passed.
+///
+/// Rust cannot place the snippets in the `em_asm` section that `EM_ASM` uses in C: on WebAssembly targets
+/// `#[link_section]` produces a custom section rather than a data segment.
+///
 /// [`js_obj!`]:   macro.js_obj.html
 /// [`js_int!`]:    macro.js_int.html
 /// [`js_double!`]: macro.js_double.html
 /// [`js_string!`]: macro.js_string.html
 /// [`js!`]:    macro.js.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
+/// [`AsmConstArgs`]:    trait.AsmConstArgs.html
 #[macro_export]
 macro_rules! __js_macro {
     ( $emscr_func:ident, $jscode:expr, $($args:expr),* ) => {
         {
-            let jscode : &'static [u8] = format!("{:?}\0", $jscode).as_bytes();
-            let arg_sigs: &[u8] = &[$((format!("{:?}", $args), b'd').1 ),*];
+            const JSCODE: &str = $crate::__js_code!($crate::__js_check!($jscode));
+            let args = ($( $crate::IntoAsmConstArg::into_asm_const_arg($args), )*);
             unsafe {
-                $crate::emscripten::$emscr_func(jscode as *const _ as *const std::os::raw::c_char, arg_sigs as *const _ as *const std::os::raw::c_char, $( $crate::JSObject::from($args).value ),* )
+                $crate::AsmConstArgs::$emscr_func(&args, JSCODE)
             }
         }
     };
 }
 
+/// Helper macro used by [`__js_macro!`], [`__js_try_macro!`] and [`js_as!`] to wrap a snippet into a
+/// NUL-terminated call to `HELPERJS.unwrapCall`.
+///
+/// The snippet is not checked here: the callers check the code they were given with `__js_check!` before
+/// wrapping it, so that errors point at the snippet as written. See [snippet checking](index.html#snippet-checking).
+/// The snippet is followed by a newline, so that a line comment at its end does not swallow the wrapper.
+///
+/// With `try`, the whole call goes through `HELPERJS.tryCall`, so that exceptions thrown while the arguments are
+/// resolved, such as stale handle errors, are caught as well as those thrown by the snippet.
+///
+/// **Should not be used directly.**
+///
+/// [`__js_macro!`]:     macro.__js_macro.html
+/// [`__js_try_macro!`]: macro.__js_try_macro.html
+/// [`js_as!`]:          macro.js_as.html
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __js_code {
+    ( try $jscode:expr ) => {
+        concat!("return HELPERJS.tryCall(function(){",
+                "return HELPERJS.unwrapCall($0, function($0, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14) {",
+                $jscode,
+                "\n}, [$1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15]);",
+                "});\0")
+    };
+    ( $jscode:expr ) => {
+        concat!("return HELPERJS.unwrapCall($0, function($0, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14) {",
+                $jscode,
+                "\n}, [$1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15]);\0")
+    };
+}
+
 /// Macro that evaluates a JavaScript code snippet with no return value and takes any number of arguments.
 ///
 /// # Arguments
 ///
-/// * `$jscode` - A `&'static str` containing the JavaScript code that needs to be run.
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
 /// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
-///                  where `std::convert::From<T> for JSObject` is implemented. They can be referenced in
-///                  JavaScript snippet as `$0`, `$1`, ...
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
 ///
-/// Note that the JavaScript snippet is responsible for unpacking the arguments itself using `HELPERJS.loadObject(...)`
-/// if the argument is not a simple number of boolean. See the documentation for [`JSObject`] for more information.
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
 ///
 /// # See also
 ///
 /// For similar macros with different return types, see [`js_int!`], [`js_double!`], [`js_string!`] or [`js_obj!`].
 ///
 /// [`JSObject`]:   struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
 /// [`js_int!`]:    macro.js_int.html
 /// [`js_double!`]: macro.js_double.html
 /// [`js_string!`]: macro.js_string.html
 /// [`js_obj!`]:    macro.js_obj.html
 #[macro_export]
 macro_rules! js {
+    ($jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => {
+        $crate::__js_named!($crate::js, $jscode, $($name = $arg),+)
+    };
     ($jscode:expr $(, $args:expr)*) => {
-        __js_macro!(emscripten_asm_const_int, $jscode, $($args),*)
+        $crate::__js_macro!(emscripten_asm_const_int, $jscode, $($args),*)
     }
 }
 
@@ -202,13 +467,16 @@ macro_rules! js {
 ///
 /// # Arguments
 ///
-/// * `$jscode` - A `&'static str` containing the JavaScript code that needs to be run.
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
 /// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
-///                  where `std::convert::From<T> for JSObject` is implemented. They can be referenced in
-///                  JavaScript snippet as `$0`, `$1`, ...
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
 ///
-/// Note that the JavaScript snippet is responsible for unpacking the arguments itself using `HELPERJS.loadObject(...)`
-/// if the argument is not a simple number of boolean. See the documentation for [`JSObject`] for more information.
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
 ///
 /// # Return value
 ///
@@ -219,22 +487,23 @@ macro_rules! js {
 /// For similar macros with different return types, see [`js_int!`], [`js_double!`], [`js_string!`] or [`js!`].
 ///
 /// [`JSObject`]:   struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
 /// [`js_int!`]:    macro.js_int.html
 /// [`js_double!`]: macro.js_double.html
 /// [`js_string!`]: macro.js_string.html
 /// [`js!`]:    macro.js.html
 #[macro_export]
-macro_rules! js_obj { // TODO: test
+macro_rules! js_obj {
+    ($jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => (
+        $crate::__js_named!($crate::js_obj, $jscode, $($name = $arg),+)
+    );
     ($jscode:expr $(, $args:expr )*) => (
-        $crate::JSObject {
-            value: __js_macro!(emscripten_asm_const_int,
-                               concat!("return HELPERJS.storeObject((function(){",
-                                       $jscode,
-                                       "})();"),
-                               $($args),*) as f64,
-            jshandle: true,
-            refcount: Rc::new(()),
-        }
+        $crate::JSObject::__from_handle(
+            $crate::__js_macro!(emscripten_asm_const_int,
+                                concat!("return HELPERJS.storeObject((function(){",
+                                        $crate::__js_check!($jscode),
+                                        "\n})());"),
+                                $($args),*) as u32)
     )
 }
 
@@ -243,13 +512,16 @@ macro_rules! js_obj { // TODO: test
 ///
 /// # Arguments
 ///
-/// * `$jscode` - A `&'static str` containing the JavaScript code that needs to be run.
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
 /// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
-///                  where `std::convert::From<T> for JSObject` is implemented. They can be referenced in
-///                  JavaScript snippet as `$0`, `$1`, ...
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
 ///
-/// Note that the JavaScript snippet is responsible for unpacking the arguments itself using `HELPERJS.loadObject(...)`
-/// if the argument is not a simple number of boolean. See the documentation for [`JSObject`] for more information.
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
 ///
 /// # Return value
 ///
@@ -260,14 +532,18 @@ macro_rules! js_obj { // TODO: test
 /// For similar macros with different return types, see [`js_int!`], [`js_double!`], [`js!`] or [`js_obj!`].
 ///
 /// [`JSObject`]:   struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
 /// [`js_int!`]:    macro.js_int.html
 /// [`js_double!`]: macro.js_double.html
 /// [`js_obj!`]: macro.js_obj.html
 /// [`js!`]:    macro.js.html
 #[macro_export]
-macro_rules! js_string { // TODO: test
+macro_rules! js_string {
+    ($jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => (
+        $crate::__js_named!($crate::js_string, $jscode, $($name = $arg),+)
+    );
     ($jscode:expr $(, $args:expr )*) => (
-        String::from(js_obj!($jscode, $($args),*))
+        ::std::string::String::from($crate::js_obj!($jscode, $($args),*))
     )
 }
 
@@ -275,13 +551,16 @@ macro_rules! js_string { // TODO: test
 ///
 /// # Arguments
 ///
-/// * `$jscode` - A `&'static str` containing the JavaScript code that needs to be run.
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
 /// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
-///                  where `std::convert::From<T> for JSObject` is implemented. They can be referenced in
-///                  JavaScript snippet as `$0`, `$1`, ...
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
 ///
-/// Note that the JavaScript snippet is responsible for unpacking the arguments itself using `HELPERJS.loadObject(...)`
-/// if the argument is not a simple number of boolean. See the documentation for [`JSObject`] for more information.
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
 ///
 /// # Return value
 ///
@@ -292,14 +571,18 @@ macro_rules! js_string { // TODO: test
 /// For similar macros with different return types, see [`js!`], [`js_double!`], [`js_string!`] or [`js_obj!`].
 ///
 /// [`JSObject`]:   struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
 /// [`js!`]:    macro.js.html
 /// [`js_double!`]: macro.js_double.html
 /// [`js_obj!`]: macro.js_obj.html
 /// [`js_string!`]: macro.js_string.html
 #[macro_export]
 macro_rules! js_int {
+    ($jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => (
+        $crate::__js_named!($crate::js_int, $jscode, $($name = $arg),+)
+    );
     ($jscode:expr $(, $args:expr )*) => (
-        __js_macro!(emscripten_asm_const_int, $jscode, $($args),*)
+        $crate::__js_macro!(emscripten_asm_const_int, $jscode, $($args),*)
     )
 }
 
@@ -308,13 +591,16 @@ macro_rules! js_int {
 ///
 /// # Arguments
 ///
-/// * `$jscode` - A `&'static str` containing the JavaScript code that needs to be run.
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
 /// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
-///                  where `std::convert::From<T> for JSObject` is implemented. They can be referenced in
-///                  JavaScript snippet as `$0`, `$1`, ...
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
 ///
-/// Note that the JavaScript snippet is responsible for unpacking the arguments itself using `HELPERJS.loadObject(...)`
-/// if the argument is not a simple number of boolean. See the documentation for [`JSObject`] for more information.
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
 ///
 /// # Return value
 ///
@@ -325,17 +611,352 @@ macro_rules! js_int {
 /// For similar macros with different return types, see [`js!`], [`js_double!`], [`js_string!`] or [`js_obj!`].
 ///
 /// [`JSObject`]:   struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
 /// [`js!`]:    macro.js.html
 /// [`js_double!`]: macro.js_double.html
 /// [`js_obj!`]: macro.js_obj.html
 /// [`js_string!`]: macro.js_string.html
 #[macro_export]
 macro_rules! js_double {
+    ($jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => (
+        $crate::__js_named!($crate::js_double, $jscode, $($name = $arg),+)
+    );
     ($jscode:expr $(, $args:expr )*) => (
-        __js_macro!(emscripten_asm_const_double, $jscode, $($args),*)
+        $crate::__js_macro!(emscripten_asm_const_double, $jscode, $($args),*)
     )
 }
 
+/// Macro that evaluates a JavaScript code snippet and converts its result into the type `$type`.
+///
+/// This is the generic version of [`js!`], [`js_int!`], [`js_double!`], [`js_string!`] and [`js_obj!`]:
+/// the way the result is returned and converted is chosen by the [`FromJs`] implementation of `$type`.
+/// Numbers and booleans are returned directly, everything else goes through a [`JSObject`].
+///
+/// # Arguments
+///
+/// * `$type` - The type of the result. It must implement [`FromJs`].
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
+/// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
+///
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
+///
+/// # Return value
+///
+/// The return value of `$jscode` as a `$type`.
+///
+/// ```ignore
+/// fn width<T: FromJs>(el: &JSObject) -> T {
+///     js_as!(T, "return $0.clientWidth;", el)
+/// }
+/// ```
+///
+/// [`FromJs`]:     trait.FromJs.html
+/// [`JSObject`]:   struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
+/// [`js!`]:        macro.js.html
+/// [`js_int!`]:    macro.js_int.html
+/// [`js_double!`]: macro.js_double.html
+/// [`js_string!`]: macro.js_string.html
+/// [`js_obj!`]:    macro.js_obj.html
+#[macro_export]
+macro_rules! js_as {
+    ($type:ty, $jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => (
+        $crate::__js_named!($crate::js_as [$type], $jscode, $($name = $arg),+)
+    );
+    ($type:ty, $jscode:expr $(, $args:expr )*) => (
+        {
+            const JSCODE: $crate::JsCode = $crate::JsCode {
+                value: $crate::__js_code!($crate::__js_check!($jscode)),
+                object: $crate::__js_code!(concat!("return HELPERJS.storeObject((function(){", $jscode, "\n})());")),
+                boolean: $crate::__js_code!(concat!("return (function(){", $jscode, "\n})() ? 1 : 0;")),
+            };
+            let args = ($( $crate::IntoAsmConstArg::into_asm_const_arg($args), )*);
+            unsafe {
+                <$type as $crate::FromJs>::__eval(&JSCODE, &args)
+            }
+        }
+    )
+}
+
+/// Helper macro used by [`js_try!`], [`js_try_int!`], [`js_try_double!`], [`js_try_string!`] or [`js_try_obj!`].
+///
+/// **Should not be used directly.**
+///
+/// [`js_try!`]:        macro.js_try.html
+/// [`js_try_int!`]:    macro.js_try_int.html
+/// [`js_try_double!`]: macro.js_try_double.html
+/// [`js_try_string!`]: macro.js_try_string.html
+/// [`js_try_obj!`]:    macro.js_try_obj.html
+#[macro_export]
+macro_rules! __js_try_macro {
+    ( $emscr_func:ident, $jscode:expr, $($args:expr),* ) => {
+        {
+            const JSCODE: &str = $crate::__js_code!(try $crate::__js_check!($jscode));
+            let args = ($( $crate::IntoAsmConstArg::into_asm_const_arg($args), )*);
+            let result = unsafe {
+                $crate::AsmConstArgs::$emscr_func(&args, JSCODE)
+            };
+            match $crate::JSError::take() {
+                ::std::option::Option::Some(error) => ::std::result::Result::Err(error),
+                ::std::option::Option::None => ::std::result::Result::Ok(result),
+            }
+        }
+    };
+}
+
+/// Macro that evaluates a JavaScript code snippet with no return value, catching any exception it throws.
+///
+/// # Arguments
+///
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
+/// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
+///
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
+///
+/// # Return value
+///
+/// `Ok(())`, or the thrown exception as a [`JSError`]. Exceptions thrown while the arguments are converted,
+/// such as stale handle errors, are caught as well.
+///
+/// # See also
+///
+/// For the version that does not catch exceptions, see [`js!`]. For similar macros with different return types,
+/// see [`js_try_int!`], [`js_try_double!`], [`js_try_string!`] or [`js_try_obj!`].
+///
+/// [`JSObject`]:       struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
+/// [`JSError`]:        struct.JSError.html
+/// [`js!`]:            macro.js.html
+/// [`js_try_int!`]:    macro.js_try_int.html
+/// [`js_try_double!`]: macro.js_try_double.html
+/// [`js_try_string!`]: macro.js_try_string.html
+/// [`js_try_obj!`]:    macro.js_try_obj.html
+#[macro_export]
+macro_rules! js_try {
+    ($jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => (
+        $crate::__js_named!($crate::js_try, $jscode, $($name = $arg),+)
+    );
+    ($jscode:expr $(, $args:expr)*) => (
+        $crate::__js_try_macro!(emscripten_asm_const_int, $jscode, $($args),*).map(|_| ())
+    )
+}
+
+/// Macro that evaluates a JavaScript code snippet which returns a JavaScript object, catching any exception it throws.
+///
+/// # Arguments
+///
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
+/// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
+///
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
+///
+/// # Return value
+///
+/// A [`JSObject`] wrapping the return value of the executed JavaScript, or the thrown exception as a [`JSError`].
+/// Exceptions thrown while the arguments are converted, such as stale handle errors, are caught as well.
+///
+/// # See also
+///
+/// For the version that does not catch exceptions, see [`js_obj!`]. For similar macros with different return types,
+/// see [`js_try!`], [`js_try_int!`], [`js_try_double!`] or [`js_try_string!`].
+///
+/// [`JSObject`]:       struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
+/// [`JSError`]:        struct.JSError.html
+/// [`js_obj!`]:        macro.js_obj.html
+/// [`js_try!`]:        macro.js_try.html
+/// [`js_try_int!`]:    macro.js_try_int.html
+/// [`js_try_double!`]: macro.js_try_double.html
+/// [`js_try_string!`]: macro.js_try_string.html
+#[macro_export]
+macro_rules! js_try_obj {
+    ($jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => (
+        $crate::__js_named!($crate::js_try_obj, $jscode, $($name = $arg),+)
+    );
+    ($jscode:expr $(, $args:expr )*) => (
+        // A match rather than `map`, so that the handle is recorded with the location of the caller.
+        match $crate::__js_try_macro!(emscripten_asm_const_int,
+                                      concat!("return HELPERJS.storeObject((function(){",
+                                              $crate::__js_check!($jscode),
+                                              "\n})());"),
+                                      $($args),*) {
+            ::std::result::Result::Ok(handle) => ::std::result::Result::Ok($crate::JSObject::__from_handle(handle as u32)),
+            ::std::result::Result::Err(error) => ::std::result::Result::Err(error),
+        }
+    )
+}
+
+/// Macro that evaluates a JavaScript code snippet which returns a string, catching any exception it throws.
+///
+/// # Arguments
+///
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
+/// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
+///
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
+///
+/// # Return value
+///
+/// A `std::string::String` converted from the JavaScript string returned by `$jscode`, or the thrown exception
+/// as a [`JSError`].
+///
+/// # See also
+///
+/// For the version that does not catch exceptions, see [`js_string!`]. For similar macros with different return types,
+/// see [`js_try!`], [`js_try_int!`], [`js_try_double!`] or [`js_try_obj!`].
+///
+/// [`JSObject`]:       struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
+/// [`JSError`]:        struct.JSError.html
+/// [`js_string!`]:     macro.js_string.html
+/// [`js_try!`]:        macro.js_try.html
+/// [`js_try_int!`]:    macro.js_try_int.html
+/// [`js_try_double!`]: macro.js_try_double.html
+/// [`js_try_obj!`]:    macro.js_try_obj.html
+#[macro_export]
+macro_rules! js_try_string {
+    ($jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => (
+        $crate::__js_named!($crate::js_try_string, $jscode, $($name = $arg),+)
+    );
+    ($jscode:expr $(, $args:expr )*) => (
+        $crate::js_try_obj!($jscode, $($args),*).map(::std::string::String::from)
+    )
+}
+
+/// Macro that evaluates a JavaScript code snippet which returns an integer, catching any exception it throws.
+///
+/// # Arguments
+///
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
+/// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
+///
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
+///
+/// # Return value
+///
+/// The return value of `$jscode` as an `i32`, or the thrown exception as a [`JSError`]. Exceptions thrown while
+/// the arguments are converted, such as stale handle errors, are caught as well.
+///
+/// # See also
+///
+/// For the version that does not catch exceptions, see [`js_int!`]. For similar macros with different return types,
+/// see [`js_try!`], [`js_try_double!`], [`js_try_string!`] or [`js_try_obj!`].
+///
+/// [`JSObject`]:       struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
+/// [`JSError`]:        struct.JSError.html
+/// [`js_int!`]:        macro.js_int.html
+/// [`js_try!`]:        macro.js_try.html
+/// [`js_try_double!`]: macro.js_try_double.html
+/// [`js_try_string!`]: macro.js_try_string.html
+/// [`js_try_obj!`]:    macro.js_try_obj.html
+#[macro_export]
+macro_rules! js_try_int {
+    ($jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => (
+        $crate::__js_named!($crate::js_try_int, $jscode, $($name = $arg),+)
+    );
+    ($jscode:expr $(, $args:expr )*) => (
+        $crate::__js_try_macro!(emscripten_asm_const_int, $jscode, $($args),*)
+    )
+}
+
+/// Macro that evaluates a JavaScript code snippet which returns a floating point number, catching any exception
+/// it throws.
+///
+/// # Arguments
+///
+/// * `$jscode` - A string literal containing the JavaScript code that needs to be run.
+/// * `$args, ...` - Any number of arguments to be used by `$jscode`. All arguments must be of a type `T`
+///   where [`IntoAsmConstArg`] is implemented. They can be referenced in
+///   JavaScript snippet as `$0`, `$1`, ...
+/// * `$name = $arg, ...` - Alternatively, any number of named arguments, referenced in `$jscode` as `{name}`.
+///   See [named arguments](index.html#named-arguments).
+///
+/// The arguments are converted to JavaScript values before the snippet runs: a [`JSObject`] is replaced by the
+/// object it refers to, strings by JavaScript strings, and booleans by `true` or `false`. At most 15 arguments
+/// can be passed.
+///
+/// # Return value
+///
+/// The return value of `$jscode` as an `f64`, or the thrown exception as a [`JSError`]. Exceptions thrown while
+/// the arguments are converted, such as stale handle errors, are caught as well.
+///
+/// # See also
+///
+/// For the version that does not catch exceptions, see [`js_double!`]. For similar macros with different return types,
+/// see [`js_try!`], [`js_try_int!`], [`js_try_string!`] or [`js_try_obj!`].
+///
+/// [`JSObject`]:       struct.JSObject.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
+/// [`JSError`]:        struct.JSError.html
+/// [`js_double!`]:     macro.js_double.html
+/// [`js_try!`]:        macro.js_try.html
+/// [`js_try_int!`]:    macro.js_try_int.html
+/// [`js_try_string!`]: macro.js_try_string.html
+/// [`js_try_obj!`]:    macro.js_try_obj.html
+#[macro_export]
+macro_rules! js_try_double {
+    ($jscode:literal, $($name:ident = $arg:expr),+ $(,)?) => (
+        $crate::__js_named!($crate::js_try_double, $jscode, $($name = $arg),+)
+    );
+    ($jscode:expr $(, $args:expr )*) => (
+        $crate::__js_try_macro!(emscripten_asm_const_double, $jscode, $($args),*)
+    )
+}
+
+mod asm_const;
+mod convert;
+mod error;
+mod heap_view;
+mod object;
+mod scope;
+mod value;
+mod weak;
+
+pub mod handles;
+
+pub use asm_const::{AsmConstArg, AsmConstArgs, IntoAsmConstArg};
+pub use convert::{AsNumber, FromJs, IntoJs, Nullish, RefIntoJs, TryFromJs, TypedArrayElement};
+#[doc(hidden)]
+pub use convert::JsCode;
+pub use emscripten_helper_macros::{js_function, js_module};
+#[doc(hidden)]
+pub use emscripten_helper_macros::{__js_check, __js_named};
+pub use error::JSError;
+pub use heap_view::HeapView;
+pub use scope::{scope, HandleScope};
+pub use value::{JSKind, JSValue};
+pub use weak::JSWeak;
 
 /// A reference to a JavaScript object.
 ///
@@ -346,7 +967,9 @@ macro_rules! js_double {
 /// be reclaimed by the JavaScript garbage collector.
 ///
 /// If you wish to add a type `T` that can be passed to JavaScript, you should
-/// implement `std::convert::From<T> for JSObject`.
+/// implement [`RefIntoJs`] or `std::convert::From<T> for JSObject`, and [`IntoAsmConstArg`]
+/// to pass it directly as a macro argument. To convert JavaScript values into `T`, implement
+/// [`FromJs`] and [`TryFromJs`].
 ///
 /// # Important note: 
 ///
@@ -355,23 +978,170 @@ macro_rules! js_double {
 ///
 /// [`HELPERJS`]: index.html#emscripten-helper-global
 /// [`js!`]:  macro.js.html
+/// [`RefIntoJs`]: trait.RefIntoJs.html
+/// [`FromJs`]: trait.FromJs.html
+/// [`TryFromJs`]: trait.TryFromJs.html
+/// [`IntoAsmConstArg`]: trait.IntoAsmConstArg.html
 #[derive(Debug, Clone)]
 pub struct JSObject {
     pub value: f64,
-    jshandle: bool,
-    refcount: Rc<()>,
+    repr: Repr,
+    // Shared by all clones. The strong count tells when the last clone is dropped, and the flag
+    // tells whether that should release the handle or something else (e.g. a scope) takes care of it.
+    refcount: Rc<Cell<bool>>,
+}
+
+// What the `value` of a `JSObject` holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Repr {
+    // A handle into the HELPERJS object table.
+    Handle,
+    // A number, passed by value.
+    Number,
+    // A boolean, passed by value as 0 or 1.
+    Bool,
 }
 
-impl<'a> std::convert::From<&'a JSObject> for JSObject {
-    fn from(v: &'a JSObject) -> Self {
-        JSObject::from(v.clone())
+impl JSObject {
+    /// Returns a `JSObject` referring to the JavaScript value `undefined`.
+    #[track_caller]
+    pub fn undefined() -> JSObject {
+        js_obj!("return undefined;")
+    }
+
+    /// Returns a `JSObject` referring to the JavaScript value `null`.
+    #[track_caller]
+    pub fn null() -> JSObject {
+        js_obj!("return null;")
+    }
+
+    /// Wraps a handle into the [`HELPERJS`] object table, as returned by `HELPERJS.storeObject`.
+    ///
+    /// **Should not be used directly.** It is only public so that it can be used by [`js_obj!`].
+    ///
+    /// [`HELPERJS`]: index.html#emscripten-helper-global
+    /// [`js_obj!`]:  macro.js_obj.html
+    #[doc(hidden)]
+    #[track_caller]
+    pub fn __from_handle(handle: u32) -> JSObject {
+        handles::record(handle, std::panic::Location::caller());
+        let release_on_drop = !scope::adopt(handle);
+        JSObject {
+            value: handle as f64,
+            repr: Repr::Handle,
+            refcount: Rc::new(Cell::new(release_on_drop)),
+        }
+    }
+
+    // Whether `value` is a handle into the object table rather than a number or a boolean.
+    fn is_handle(&self) -> bool {
+        self.repr == Repr::Handle
+    }
+
+    /// Returns whether this object still refers to a live entry in the [`HELPERJS`] object table.
+    ///
+    /// This is always the case for a `JSObject` obtained through the safe API of this crate. It can only
+    /// be false if the handle was released behind the back of this `JSObject`.
+    ///
+    /// Objects that are not stored in the object table, such as numbers converted from Rust, are always valid.
+    ///
+    /// [`HELPERJS`]: index.html#emscripten-helper-global
+    pub fn is_valid(&self) -> bool {
+        !self.is_handle() || js_int!("return HELPERJS.isValidHandle($0) ? 1 : 0;", self.value) != 0
+    }
+
+    // With the `debug-handles` feature, panics at the caller if this object refers to a released handle,
+    // instead of letting JavaScript throw on a stale handle. Called wherever an object is handed to JavaScript.
+    #[track_caller]
+    fn check_valid(&self) {
+        #[cfg(feature = "debug-handles")]
+        assert!(self.is_valid(), "use of stale JavaScript handle {:#010x}", self.value as u32);
+    }
+
+    /// Consumes the `JSObject` and returns its raw [handle](index.html#handles) without releasing it.
+    ///
+    /// The handle stays in the object table until it is turned back into a `JSObject` with
+    /// [`JSObject::from_raw`] and that object is dropped, or until it is released from C with `emh_release`.
+    /// Other clones of this object remain usable, but none of them will release the handle anymore.
+    ///
+    /// The handle can be stored anywhere an `u32` fits, including C structs. C and C++ code linked into the
+    /// same emscripten module can use it with `HELPERJS.loadObject` or the functions declared in
+    /// `include/emscripten_helper.h`.
+    ///
+    /// A `JSObject` that is not stored in the object table, such as a number converted from Rust, is stored first.
+    /// If the object belongs to a [`HandleScope`], it is taken out of the scope.
+    ///
+    /// The handle is still alive, so the [`handles`] leak tracker keeps reporting it until it is released. Use
+    /// [`JSObject::leak`] for handles that are never meant to be reclaimed.
+    ///
+    /// [`JSObject::from_raw`]: struct.JSObject.html#method.from_raw
+    /// [`JSObject::leak`]:     struct.JSObject.html#method.leak
+    /// [`HandleScope`]:        struct.HandleScope.html
+    /// [`handles`]:            handles/index.html
+    #[track_caller]
+    pub fn into_raw(self) -> u32 {
+        let obj = if self.is_handle() { self } else { js_obj!("return $0;", self) };
+        let handle = obj.value as u32;
+        obj.refcount.set(false);
+        scope::disown(handle);
+        handle
+    }
+
+    /// Takes ownership of a raw handle, as returned by [`JSObject::into_raw`] or `HELPERJS.storeObject`.
+    ///
+    /// The returned `JSObject` releases the handle when the last clone of it is dropped. The handle is not adopted
+    /// by an enclosing [`scope`], since raw handles usually come from storage that outlives it.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be a handle that is not owned by another `JSObject`. Taking ownership of the same handle
+    /// twice leads to a double release, and to stale handle errors when the remaining object is used.
+    ///
+    /// [`JSObject::into_raw`]: struct.JSObject.html#method.into_raw
+    /// [`scope`]:              fn.scope.html
+    #[track_caller]
+    pub unsafe fn from_raw(handle: u32) -> JSObject {
+        let obj = JSObject::__from_handle(handle);
+        scope::release_on_drop(&obj);
+        obj
+    }
+
+    /// Consumes the `JSObject` and keeps the JavaScript object alive for the rest of the program, returning its
+    /// raw [handle](index.html#handles).
+    ///
+    /// This is [`JSObject::into_raw`] for handles that are never meant to be reclaimed, such as objects
+    /// shared with C code for the lifetime of the module. The returned handle is ignored by the
+    /// [`handles`] leak tracker.
+    ///
+    /// [`JSObject::into_raw`]: struct.JSObject.html#method.into_raw
+    /// [`handles`]:            handles/index.html
+    #[track_caller]
+    pub fn leak(self) -> u32 {
+        let handle = self.into_raw();
+        handles::forget(handle);
+        handle
+    }
+}
+
+impl<'a, T: RefIntoJs + ?Sized> std::convert::From<&'a T> for JSObject {
+    #[track_caller]
+    fn from(v: &'a T) -> Self {
+        v.to_js()
     }
 }
 
 impl Drop for JSObject {
     fn drop(&mut self) {
-        if self.jshandle && Rc::strong_count(&self.refcount) == 1 {
-            js!("HELPERJS.releaseObject($0);", self.value);
+        if self.is_handle() && self.refcount.get() && Rc::strong_count(&self.refcount) == 1 {
+            let released = js_int!("return HELPERJS.releaseObject($0);", self.value);
+            handles::forget(self.value as u32);
+
+            #[cfg(feature = "debug-handles")]
+            if released == 0 && !std::thread::panicking() {
+                panic!("double release of JavaScript handle {:#010x}", self.value as u32);
+            }
+            #[cfg(not(feature = "debug-handles"))]
+            let _ = released;
             
             // let code : &'static [u8] = b"HELPERJS.releaseObject($0);\0";
             // unsafe {
@@ -382,25 +1152,61 @@ impl Drop for JSObject {
 }
 
 impl<T> std::convert::From<Vec<T>> for JSObject
-    where JSObject: std::convert::From<T> {
+    where T: IntoJs {
+    #[track_caller]
     fn from(v: Vec<T>) -> Self {
-        let arr = js_obj!("return HELPERJS.storeObject([]);");
-        for elem in v {
-            let elem_js = JSObject::from(elem);
-            let code = if elem_js.jshandle {
-                "HELPERJS.loadObject($0).push(HELPERJS.loadObject($1))"
-            } else {
-                "HELPERJS.loadObject($0).push($1)"
-            };
+        // Numeric `Vec`s become typed arrays in a single copy, like borrowed slices.
+        if let Some(array) = convert::typed_array_of::<T>() {
+            return js_obj!("return HELPERJS.copyArrayFromHeap($0, $1, $2);", array, v.as_ptr(), v.len());
+        }
+        // Converted in a loop, so that the handles of the elements are recorded with the location of the caller.
+        let mut objects = Vec::with_capacity(v.len());
+        for value in v {
+            objects.push(value.into_js());
+        }
+        array_from_objects(&objects)
+    }
+}
 
-            js!(code, arr.value, elem_js.value);
+// Builds a JavaScript array of the values referred to by `objects` in a single call.
+#[track_caller]
+fn array_from_objects(objects: &[JSObject]) -> JSObject {
+    let values: Vec<f64> = objects.iter().map(|obj| obj.value).collect();
+    let kinds: Vec<u8> = objects.iter().map(|obj| obj.kind_code() as u8).collect();
+    js_obj!("return HELPERJS.makeArray($0, $1, $2);",
+            values.as_ptr(), kinds.as_ptr(), objects.len())
+}
 
-            // unsafe {
-            //     emscripten::emscripten_asm_const_int(code as *const _ as *const std::os::raw::c_char,
-            //                                          arr.value, elem_js.value);
-            // }
+impl<T> std::convert::From<Option<T>> for JSObject
+    where T: IntoJs {
+    #[track_caller]
+    fn from(v: Option<T>) -> Self {
+        match v {
+            Some(value) => value.into_js(),
+            None => JSObject::null(),
+        }
+    }
+}
+
+impl<T> std::convert::From<Nullish<T>> for JSObject
+    where T: IntoJs {
+    #[track_caller]
+    fn from(v: Nullish<T>) -> Self {
+        match v {
+            Nullish::Undefined => JSObject::undefined(),
+            Nullish::Null => JSObject::null(),
+            Nullish::Value(value) => value.into_js(),
         }
-        arr
+    }
+}
+
+impl<T> std::convert::TryFrom<JSObject> for Vec<T>
+    where T: TryFromJs {
+    type Error = JSError;
+
+    #[track_caller]
+    fn try_from(obj: JSObject) -> Result<Self, JSError> {
+        Vec::try_from_js(&obj)
     }
 }
 
@@ -411,20 +1217,15 @@ macro_rules! __js_from_numeric {
                 fn from(v: $type) -> Self {
                     JSObject {
                         value: v as f64,
-                        jshandle: false,
-                        refcount: Rc::new(()),
+                        repr: Repr::Number,
+                        refcount: Rc::new(Cell::new(false)),
                     }
                 }
             }
 
             impl std::convert::From<JSObject> for $type {
                 fn from(obj: JSObject) -> Self {
-                    if obj.jshandle {
-                        js_double!("return HELPERJS.loadObject($0);",
-                                   obj) as $type
-                    } else {
-                        obj.value as $type
-                    }
+                    <$type>::from_js(&obj)
                 }
             }
         )+
@@ -433,39 +1234,64 @@ macro_rules! __js_from_numeric {
 
 __js_from_numeric!(isize, usize, i32, u32, i16, u16, i8, u8, f32, f64);
 
-impl<'a> std::convert::From<&'a str> for JSObject {
-    fn from(s: &'a str) -> Self { // TODO: This won't work when the pointer can't fit in 31bit int.
-        // let code : &'static [u8] = b"return HELPERJS.storeObject(HELPERJS.copyStringFromHeap($0, $1));\0";
-        let data : Vec<u16> = s.encode_utf16().collect();
-        let data_ptr_as_isize = data.as_ptr() as isize;
-        let value = js_int!("return HELPERJS.storeObject(HELPERJS.copyStringFromHeap($0, $1));", data_ptr_as_isize, data.len() as f64) as f64;
-        unsafe {
-            JSObject {
-                value: value,//emscripten::emscripten_asm_const_int(code as *const _ as *const std::os::raw::c_char, data_ptr_as_isize, data.len()) as f64,
-                jshandle: true,
-                refcount: Rc::new(()),
+macro_rules! __js_from_bigint {
+    ( $( $type:ty ),+ ) => (
+        $(
+            impl std::convert::From<AsNumber<$type>> for JSObject {
+                fn from(v: AsNumber<$type>) -> Self {
+                    JSObject::from(v.0 as f64)
+                }
             }
-        }
+
+            impl std::convert::From<JSObject> for $type {
+                fn from(obj: JSObject) -> Self {
+                    <$type>::from_js(&obj)
+                }
+            }
+        )+
+    )
+}
+
+__js_from_bigint!(i64, u64, i128, u128);
+
+impl std::convert::From<i64> for JSObject {
+    #[track_caller]
+    fn from(v: i64) -> Self {
+        js_obj!("return $0;", v)
     }
 }
 
-impl<'a> std::convert::From<&'a String> for JSObject {
-    fn from(s: &'a String) -> Self {
-        JSObject::from(s.as_str())
+impl std::convert::From<u64> for JSObject {
+    #[track_caller]
+    fn from(v: u64) -> Self {
+        js_obj!("return $0;", v)
+    }
+}
+
+impl std::convert::From<i128> for JSObject {
+    #[track_caller]
+    fn from(v: i128) -> Self {
+        js_obj!("return ($0 << 64n) | $1;", (v >> 64) as i64, v as u64)
+    }
+}
+
+impl std::convert::From<u128> for JSObject {
+    #[track_caller]
+    fn from(v: u128) -> Self {
+        js_obj!("return ($0 << 64n) | $1;", (v >> 64) as u64, v as u64)
     }
 }
 
 impl std::convert::From<String> for JSObject {
+    #[track_caller]
     fn from(s: String) -> Self {
-        JSObject::from(s.as_str())
+        s.to_js()
     }
 }
 
 impl std::convert::From<JSObject> for String {
     fn from(obj: JSObject) -> Self {
-        let ptr = js_int!("return HELPERJS.storeObject(HELPERJS.copyStringToHeap(HELPERJS.loadObject($0)));",
-                          obj) as *mut u16;
-        string_from_js(ptr)
+        String::from_js(&obj)
     }
 }
 
@@ -473,28 +1299,155 @@ impl std::convert::From<bool> for JSObject {
     fn from(b: bool) -> Self {
         JSObject {
             value: if b { 1.0 } else { 0.0 },
-            jshandle: false,
-            refcount: Rc::new(()),
+            repr: Repr::Bool,
+            refcount: Rc::new(Cell::new(false)),
         }
     }
 }
 
 impl std::convert::From<JSObject> for bool {
     fn from(obj: JSObject) -> Self {
-        if obj.jshandle {
-            js_int!("return HELPERJS.loadObject($0);",
-                    obj) != 0
-        } else {
-            obj.value != 0f64
-        }
+        bool::from_js(&obj)
     }
 }
 
+/// A list of arguments for a JavaScript function call.
+///
+/// Implemented for tuples of up to 12 elements, where each element implements [`IntoJs`].
+/// Use `()` to pass no arguments and `(x,)` to pass a single argument.
+///
+/// # See also
+///
+/// [`JSObject::call`], [`JSObject::call_method`] and [`JSObject::construct`].
+///
+/// [`JSObject::call`]:        struct.JSObject.html#method.call
+/// [`JSObject::call_method`]: struct.JSObject.html#method.call_method
+/// [`IntoJs`]:                trait.IntoJs.html
+/// [`JSObject::construct`]:   struct.JSObject.html#method.construct
+pub trait JSArgs {
+    /// Converts the arguments into a JavaScript array.
+    fn into_js_array(self) -> JSObject;
+}
+
+macro_rules! __js_args_tuple {
+    ( $jscode:literal $(, $name:ident )* ) => (
+        impl<$( $name ),*> JSArgs for ($( $name, )*)
+            where $( $name: IntoJs ),* {
+            #[allow(non_snake_case)]
+            #[track_caller]
+            fn into_js_array(self) -> JSObject {
+                let ($( $name, )*) = self;
+                js_obj!($jscode $(, $name.into_js() )*)
+            }
+        }
+    )
+}
+
+__js_args_tuple!("return [];");
+__js_args_tuple!("return [$0];", A);
+__js_args_tuple!("return [$0, $1];", A, B);
+__js_args_tuple!("return [$0, $1, $2];", A, B, C);
+__js_args_tuple!("return [$0, $1, $2, $3];", A, B, C, D);
+__js_args_tuple!("return [$0, $1, $2, $3, $4];", A, B, C, D, E);
+__js_args_tuple!("return [$0, $1, $2, $3, $4, $5];", A, B, C, D, E, F);
+__js_args_tuple!("return [$0, $1, $2, $3, $4, $5, $6];", A, B, C, D, E, F, G);
+__js_args_tuple!("return [$0, $1, $2, $3, $4, $5, $6, $7];", A, B, C, D, E, F, G, H);
+__js_args_tuple!("return [$0, $1, $2, $3, $4, $5, $6, $7, $8];", A, B, C, D, E, F, G, H, I);
+__js_args_tuple!("return [$0, $1, $2, $3, $4, $5, $6, $7, $8, $9];", A, B, C, D, E, F, G, H, I, J);
+__js_args_tuple!("return [$0, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10];", A, B, C, D, E, F, G, H, I, J, K);
+__js_args_tuple!("return [$0, $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11];", A, B, C, D, E, F, G, H, I, J, K, L);
+
 /// Initializes the JavaScript [HELPERJS global object and helper functions](index.html#javascript-helpers).
 /// Should be called before using any other functions or macros from this crate.
+///
+/// This also installs the modules declared with [`js_module!`] into `HELPERJS.modules`. Calling it again
+/// resets `HELPERJS` and installs the modules again.
+///
+/// [`js_module!`]: macro.js_module.html
 pub fn init() {
     js_eval(concat!(include_str!(concat!(env!("OUT_DIR"), "/helper.js")),
-                    "\0").as_bytes())
+                    "\0").as_bytes());
+    // Copied, so that an install function may declare more modules without deadlocking.
+    let modules = MODULES.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    for (_, install) in modules {
+        install();
+    }
+}
+
+// The name of a module declared with `js_module!`, and the function installing it.
+type Module = (&'static str, fn());
+
+// The modules declared with `js_module!`. They register themselves before `main` runs.
+static MODULES: Mutex<Vec<Module>> = Mutex::new(Vec::new());
+
+/// Registers a module declared with [`js_module!`], to be installed by [`init`].
+///
+/// **Should not be used directly.** It is only public so that it can be used by [`js_module!`].
+///
+/// [`init`]:       fn.init.html
+/// [`js_module!`]: macro.js_module.html
+#[doc(hidden)]
+pub fn __register_module(name: &'static str, install: fn()) {
+    MODULES.lock().unwrap_or_else(PoisonError::into_inner).push((name, install));
+}
+
+/// Returns the names of the modules declared with [`js_module!`], in the order [`init`] installs them.
+///
+/// **Should not be used directly.** It is only public so that the registration can be tested.
+///
+/// [`init`]:       fn.init.html
+/// [`js_module!`]: macro.js_module.html
+#[doc(hidden)]
+pub fn __registered_modules() -> Vec<&'static str> {
+    MODULES.lock().unwrap_or_else(PoisonError::into_inner).iter().map(|&(name, _)| name).collect()
+}
+
+/// Helper macro used by [`js_module!`] to register a module with [`init`] before `main` runs, by placing a
+/// function in the section of static constructors.
+///
+/// **Should not be used directly.**
+///
+/// [`init`]:       fn.init.html
+/// [`js_module!`]: macro.js_module.html
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __register_module {
+    ( $name:expr, $install:path ) => {
+        #[used]
+        #[cfg_attr(any(target_os = "macos", target_os = "ios"), link_section = "__DATA,__mod_init_func")]
+        #[cfg_attr(windows, link_section = ".CRT$XCU")]
+        #[cfg_attr(not(any(target_os = "macos", target_os = "ios", windows)), link_section = ".init_array")]
+        static __REGISTER_MODULE: extern "C" fn() = {
+            extern "C" fn register() {
+                $crate::__register_module($name, $install);
+            }
+            register
+        };
+    };
+}
+
+/// Returns the JavaScript global object, `globalThis`.
+///
+/// In a browser this is the `window` object.
+#[track_caller]
+pub fn global() -> JSObject {
+    js_obj!("return globalThis;")
+}
+
+/// Looks up a global JavaScript value by its dotted path, such as `"document.body"` or `"Module.canvas"`.
+///
+/// A leading `Module` refers to the emscripten `Module` object, even when it is not a global.
+///
+/// # Return value
+///
+/// A [`JSObject`] referring to the value, or a `ReferenceError` as a [`JSError`] naming the first segment of the
+/// path that does not exist. A segment that exists but holds `undefined` is not an error.
+///
+/// [`JSObject`]: struct.JSObject.html
+/// [`JSError`]:  struct.JSError.html
+#[track_caller]
+pub fn lookup(path: &str) -> Result<JSObject, JSError> {
+    js_try_obj!("return HELPERJS.lookup($0);", path)
 }
 
 // Rust safe-wrapper using emscripten_set_main_loop_arg
@@ -516,4 +1469,28 @@ pub fn set_main_loop<F: FnMut() + 'static>(
         let callback_ref = unsafe { &mut *leaked_pointer };
         callback_ref()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, not(target_os = "emscripten")))]
+mod tests {
+    use crate::host::run_node;
+
+    // Runs `setup` and then a snippet built by `__js_code!` against helper.js, passing it `args` as `$0`, `$1`, ...
+    // (starting with the packed kinds) and printing `output` with the value it returned as `result`.
+    fn run_snippet(setup: &str, code: &str, args: &str, output: &str) -> Option<String> {
+        let params: Vec<String> = (0..16).map(|idx| format!("\"${}\"", idx)).collect();
+        run_node(&format!("{}\n{}\nvar result = new Function({}, {:?})({});\nconsole.log({});",
+                          include_str!("helper.js"), setup, params.join(", "), code.trim_end_matches('\0'), args, output))
+    }
+
+    #[test]
+    fn js_try_catches_stale_argument_handles() {
+        // What `js_try!("return $0;", obj)` runs, with `obj` released before the call.
+        let output = run_snippet("var obj = HELPERJS.storeObject({}); HELPERJS.releaseObject(obj);",
+                                 __js_code!(try "return $0;"), "1, obj",
+                                 "[result, HELPERJS.HAS_ERROR, HELPERJS.LAST_ERROR.message].join(' ')");
+        if let Some(output) = output {
+            assert!(output.starts_with("0 true HELPERJS.loadObject: stale or invalid handle"), "{}", output);
+        }
+    }
+}
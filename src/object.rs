@@ -0,0 +1,172 @@
+use crate::{FromJs, IntoJs, JSArgs, JSError, JSObject};
+
+/// Property access.
+///
+/// These methods expect `self` to refer to a JavaScript object stored in the
+/// [`HELPERJS`] object table. Keys and values can be of any type `T` where
+/// `std::convert::From<T> for JSObject` is implemented.
+///
+/// [`HELPERJS`]: index.html#emscripten-helper-global
+impl JSObject {
+    /// Returns the value of the property `key`, converted to `T`.
+    ///
+    /// A missing property is returned as `undefined`.
+    #[track_caller]
+    pub fn get<T: FromJs>(&self, key: impl IntoJs) -> T {
+        let key = key.into_js();
+        T::from_js(&js_obj!("return $0[$1];", self, key))
+    }
+
+    /// Sets the property `key` to `value`.
+    pub fn set(&self, key: impl IntoJs, value: impl IntoJs) {
+        let key = key.into_js();
+        let value = value.into_js();
+        js!("$0[$1] = $2;", self, key, value);
+    }
+
+    /// Returns whether the object has a property `key`, either directly or through its prototype chain.
+    ///
+    /// This is the equivalent of the JavaScript `in` operator.
+    pub fn has(&self, key: impl IntoJs) -> bool {
+        let key = key.into_js();
+        js_int!("return ($1 in $0) ? 1 : 0;", self, key) != 0
+    }
+
+    /// Deletes the property `key`.
+    ///
+    /// # Return value
+    ///
+    /// The result of the JavaScript `delete` operator: `false` if the property could not be deleted.
+    pub fn delete(&self, key: impl IntoJs) -> bool {
+        let key = key.into_js();
+        js_int!("return (delete $0[$1]) ? 1 : 0;", self, key) != 0
+    }
+
+    /// Returns the names of the object's own enumerable properties, as returned by `Object.keys`.
+    pub fn keys(&self) -> Vec<String> {
+        let keys = js_obj!("return Object.keys($0);", self);
+        (0..keys.array_length()).map(|idx| keys.get(idx)).collect()
+    }
+
+    /// Returns the object's own enumerable properties and their values, as returned by `Object.entries`.
+    #[track_caller]
+    pub fn entries(&self) -> Vec<(String, JSObject)> {
+        let entries = js_obj!("return Object.entries($0);", self);
+        // A loop rather than a closure, which would hide the caller from the values' handles.
+        let mut result = Vec::new();
+        for idx in 0..entries.array_length() {
+            let entry: JSObject = entries.get(idx);
+            result.push((entry.get(0), entry.get(1)));
+        }
+        result
+    }
+
+    /// Returns the value at a dotted property path such as `"a.b.c"`, converted to `T`.
+    ///
+    /// If any segment of the path is missing, `undefined` is returned.
+    #[track_caller]
+    pub fn get_path<T: FromJs>(&self, path: &str) -> T {
+        T::from_js(&js_obj!("return HELPERJS.getPath($0, $1);", self, path))
+    }
+
+    fn array_length(&self) -> i32 {
+        js_int!("return $0.length;", self)
+    }
+}
+
+/// Function invocation.
+///
+/// Arguments are passed as a tuple implementing [`JSArgs`], e.g. `(1, "two", &obj)`. Use `()`
+/// to pass no arguments and `(x,)` to pass a single argument.
+///
+/// Exceptions thrown by the called function are returned as a [`JSError`].
+///
+/// [`JSArgs`]:  trait.JSArgs.html
+/// [`JSError`]: struct.JSError.html
+impl JSObject {
+    /// Calls this object as a function, with `this` set to `this_arg`.
+    ///
+    /// Pass [`JSObject::undefined`] as `this_arg` for a plain function call.
+    ///
+    /// [`JSObject::undefined`]: struct.JSObject.html#method.undefined
+    #[track_caller]
+    pub fn call<A: JSArgs>(&self, this_arg: impl IntoJs, args: A) -> Result<JSObject, JSError> {
+        let this_arg = this_arg.into_js();
+        js_try_obj!("return HELPERJS.invoke($0, $1, $2);", self, this_arg, args.into_js_array())
+    }
+
+    /// Calls the method `name` of this object.
+    #[track_caller]
+    pub fn call_method<A: JSArgs>(&self, name: &str, args: A) -> Result<JSObject, JSError> {
+        js_try_obj!("return HELPERJS.invoke($0[$1], $0, $2);", self, name, args.into_js_array())
+    }
+
+    /// Calls this object as a constructor, like the JavaScript `new` operator.
+    #[track_caller]
+    pub fn construct<A: JSArgs>(&self, args: A) -> Result<JSObject, JSError> {
+        js_try_obj!("return Reflect.construct($0, $1);", self, args.into_js_array())
+    }
+}
+
+// Must match the codes understood by `HELPERJS.compare`.
+#[derive(Clone, Copy)]
+enum Comparison {
+    StrictEquals = 0,
+    LooseEquals = 1,
+    SameValue = 2,
+    InstanceOf = 3,
+}
+
+/// Comparison.
+///
+/// Two `JSObject`s referring to the same JavaScript object may hold different handles, so comparing
+/// their `value` fields says nothing about the objects themselves. These methods compare the
+/// JavaScript values instead.
+impl JSObject {
+    /// Compares with `other` using the JavaScript `===` operator.
+    ///
+    /// This is also what `==` on `JSObject` does.
+    pub fn js_eq(&self, other: &JSObject) -> bool {
+        self.compare(other, Comparison::StrictEquals)
+    }
+
+    /// Compares with `other` using the JavaScript `==` operator, which converts between types.
+    pub fn loose_eq(&self, other: &JSObject) -> bool {
+        self.compare(other, Comparison::LooseEquals)
+    }
+
+    /// Compares with `other` using `Object.is`.
+    ///
+    /// Unlike `===`, this considers `NaN` equal to itself and `+0` different from `-0`.
+    pub fn same_value(&self, other: &JSObject) -> bool {
+        self.compare(other, Comparison::SameValue)
+    }
+
+    /// Returns whether this object is an instance of `constructor`, using the JavaScript `instanceof` operator.
+    ///
+    /// Returns `false` if `constructor` is not callable.
+    pub fn instance_of(&self, constructor: &JSObject) -> bool {
+        js_try_int!("return HELPERJS.compare($0, $1, $2) ? 1 : 0;", self, constructor, Comparison::InstanceOf as i32)
+            .map_or(false, |result| result != 0)
+    }
+
+    fn compare(&self, other: &JSObject, comparison: Comparison) -> bool {
+        if !self.is_handle() && self.repr == other.repr {
+            // Plain numbers or booleans, which all three equality operators agree on except for NaN and zeros.
+            return match comparison {
+                Comparison::SameValue => {
+                    (self.value.is_nan() && other.value.is_nan())
+                        || (self.value == other.value && self.value.is_sign_negative() == other.value.is_sign_negative())
+                }
+                _ => self.value == other.value,
+            };
+        }
+        js_int!("return HELPERJS.compare($0, $1, $2) ? 1 : 0;", self, other, comparison as i32) != 0
+    }
+}
+
+impl PartialEq for JSObject {
+    fn eq(&self, other: &JSObject) -> bool {
+        self.js_eq(other)
+    }
+}
@@ -0,0 +1,185 @@
+use std::cell::RefCell;
+
+use crate::{handles, JSObject};
+
+thread_local! {
+    // Handles owned by each active scope, innermost last.
+    static SCOPES: RefCell<Vec<Vec<u32>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A scope that owns the [`JSObject`]s created inside it, see [`scope`].
+///
+/// [`JSObject`]: struct.JSObject.html
+/// [`scope`]:    fn.scope.html
+#[derive(Debug)]
+pub struct HandleScope {
+    depth: usize,
+}
+
+impl HandleScope {
+    /// Takes `obj` out of this scope, so that it stays alive after the scope exits.
+    ///
+    /// If this scope is nested in another scope, `obj` is moved to the enclosing scope. Otherwise it
+    /// goes back to being released when the last clone of it is dropped.
+    ///
+    /// Objects that do not belong to this scope are returned unchanged.
+    pub fn escape(&self, obj: JSObject) -> JSObject {
+        if obj.is_handle() {
+            let handle = obj.value as u32;
+            SCOPES.with(|scopes| {
+                let mut scopes = scopes.borrow_mut();
+                if let Some(pos) = scopes[self.depth].iter().rposition(|&owned| owned == handle) {
+                    scopes[self.depth].swap_remove(pos);
+                    if self.depth > 0 {
+                        scopes[self.depth - 1].push(handle);
+                    } else {
+                        obj.refcount.set(true);
+                    }
+                }
+            });
+        }
+        obj
+    }
+}
+
+// Pops the scope and releases its handles, even when unwinding.
+struct ScopeGuard;
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        let owned = SCOPES.with(|scopes| scopes.borrow_mut().pop()).unwrap_or_default();
+        if owned.is_empty() {
+            return;
+        }
+
+        js_int!("return HELPERJS.releaseMany($0, $1);", owned.as_ptr() as usize, owned.len());
+        for &handle in &owned {
+            handles::forget(handle);
+        }
+    }
+}
+
+/// Runs `f` in a new [`HandleScope`], releasing every temporary JavaScript object created inside
+/// it in a single call when `f` returns.
+///
+/// While the scope is active, every [`JSObject`] created by the macros of this crate belongs to the
+/// scope instead of being released when dropped. This saves one crossing into JavaScript per
+/// temporary, which adds up in code that creates many short-lived objects, such as a render loop.
+///
+/// Objects that must outlive the scope have to be passed through [`HandleScope::escape`]. A
+/// `JSObject` that leaves the scope without being escaped refers to a released handle, and using it
+/// raises a stale handle error.
+///
+/// Scopes can be nested. Objects belong to the innermost active scope.
+///
+/// ```ignore
+/// let list = emscripten_helper_rs::scope(|s| {
+///     let list = js_obj!("return document.getElementById(\"list\");");
+///     for idx in 0..1000 {
+///         list.call_method("append", (idx.to_string(),)).unwrap();
+///     }
+///     s.escape(list)
+/// });
+/// ```
+///
+/// [`HandleScope`]:         struct.HandleScope.html
+/// [`HandleScope::escape`]: struct.HandleScope.html#method.escape
+/// [`JSObject`]:            struct.JSObject.html
+pub fn scope<R, F: FnOnce(&HandleScope) -> R>(f: F) -> R {
+    let depth = SCOPES.with(|scopes| {
+        let mut scopes = scopes.borrow_mut();
+        scopes.push(Vec::new());
+        scopes.len() - 1
+    });
+    let _guard = ScopeGuard;
+    f(&HandleScope { depth })
+}
+
+// Hands a newly created handle over to the innermost scope, if there is one.
+// Returns whether the handle was adopted.
+pub(crate) fn adopt(handle: u32) -> bool {
+    SCOPES.with(|scopes| {
+        match scopes.borrow_mut().last_mut() {
+            Some(owned) => {
+                owned.push(handle);
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+// Takes `obj` out of whichever scope owns it, so that it is released when its last clone is dropped, e.g.
+// because it is owned by a value that may outlive the scope.
+pub(crate) fn release_on_drop(obj: &JSObject) {
+    if obj.is_handle() {
+        disown(obj.value as u32);
+        obj.refcount.set(true);
+    }
+}
+
+// Takes a handle out of whichever scope owns it, e.g. because it became a raw handle.
+pub(crate) fn disown(handle: u32) {
+    SCOPES.with(|scopes| {
+        for owned in scopes.borrow_mut().iter_mut() {
+            owned.retain(|&owned| owned != handle);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owned() -> Vec<Vec<u32>> {
+        SCOPES.with(|scopes| scopes.borrow().clone())
+    }
+
+    #[test]
+    fn handles_belong_to_innermost_scope() {
+        assert!(!adopt(1), "no scope is active");
+        scope(|_| {
+            assert!(adopt(1));
+            scope(|_| {
+                assert!(adopt(2));
+                assert_eq!(owned(), [vec![1], vec![2]]);
+                disown(2);
+            });
+            assert_eq!(owned(), [vec![1]]);
+            disown(1);
+        });
+        assert!(owned().is_empty());
+    }
+
+    #[test]
+    fn escape_keeps_values_passed_by_value() {
+        scope(|s| {
+            let obj = s.escape(JSObject::from(3));
+            assert_eq!(obj.value, 3.0);
+            assert!(owned()[0].is_empty());
+        });
+    }
+
+    #[test]
+    fn release_on_drop_takes_handles_out_of_scopes() {
+        scope(|_| {
+            let obj = JSObject::__from_handle(5);
+            assert_eq!(owned(), [vec![5]]);
+            release_on_drop(&obj);
+            assert!(owned()[0].is_empty());
+            assert!(obj.refcount.get());
+            // Dropping it would release the handle in JavaScript.
+            std::mem::forget(obj);
+        });
+    }
+
+    #[test]
+    fn raw_handles_are_not_adopted() {
+        scope(|_| {
+            let obj = unsafe { JSObject::from_raw(6) };
+            assert!(owned()[0].is_empty());
+            assert!(obj.refcount.get());
+            std::mem::forget(obj);
+        });
+    }
+}
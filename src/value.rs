@@ -0,0 +1,149 @@
+use crate::{JSObject, Repr};
+
+/// The type of a JavaScript value, as reported by [`JSObject::kind`].
+///
+/// This follows the JavaScript `typeof` operator, except that `null` and arrays get their own
+/// variants instead of being reported as objects.
+///
+/// [`JSObject::kind`]: struct.JSObject.html#method.kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JSKind {
+    Undefined,
+    Null,
+    Bool,
+    Number,
+    String,
+    BigInt,
+    Symbol,
+    Array,
+    Function,
+    Object,
+}
+
+impl JSKind {
+    // Must match the codes returned by `HELPERJS.typeOf`.
+    fn from_code(code: i32) -> JSKind {
+        match code {
+            0 => JSKind::Undefined,
+            1 => JSKind::Null,
+            2 => JSKind::Bool,
+            3 => JSKind::Number,
+            4 => JSKind::String,
+            5 => JSKind::BigInt,
+            6 => JSKind::Symbol,
+            7 => JSKind::Array,
+            8 => JSKind::Function,
+            _ => JSKind::Object,
+        }
+    }
+
+    // The kind as it reads in error messages, e.g. "a number".
+    pub(crate) fn describe(self) -> &'static str {
+        match self {
+            JSKind::Undefined => "undefined",
+            JSKind::Null => "null",
+            JSKind::Bool => "a boolean",
+            JSKind::Number => "a number",
+            JSKind::String => "a string",
+            JSKind::BigInt => "a BigInt",
+            JSKind::Symbol => "a symbol",
+            JSKind::Array => "an array",
+            JSKind::Function => "a function",
+            JSKind::Object => "an object",
+        }
+    }
+}
+
+/// A JavaScript value converted according to its type, as returned by [`JSObject::to_value`].
+///
+/// Primitive values that have a Rust equivalent are converted. Everything else keeps a
+/// [`JSObject`] referring to the original JavaScript value.
+///
+/// [`JSObject`]:           struct.JSObject.html
+/// [`JSObject::to_value`]: struct.JSObject.html#method.to_value
+#[derive(Debug, Clone)]
+pub enum JSValue {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    BigInt(JSObject),
+    Symbol(JSObject),
+    Array(JSObject),
+    Function(JSObject),
+    Object(JSObject),
+}
+
+impl JSValue {
+    /// Returns the [`JSKind`] of this value.
+    ///
+    /// [`JSKind`]: enum.JSKind.html
+    pub fn kind(&self) -> JSKind {
+        match *self {
+            JSValue::Undefined => JSKind::Undefined,
+            JSValue::Null => JSKind::Null,
+            JSValue::Bool(_) => JSKind::Bool,
+            JSValue::Number(_) => JSKind::Number,
+            JSValue::String(_) => JSKind::String,
+            JSValue::BigInt(_) => JSKind::BigInt,
+            JSValue::Symbol(_) => JSKind::Symbol,
+            JSValue::Array(_) => JSKind::Array,
+            JSValue::Function(_) => JSKind::Function,
+            JSValue::Object(_) => JSKind::Object,
+        }
+    }
+}
+
+impl JSObject {
+    /// Returns the type of the JavaScript value referred to by this object.
+    ///
+    /// Values converted from Rust numbers or booleans are not stored in the [`HELPERJS`] object table,
+    /// and their kind is known without calling into JavaScript.
+    ///
+    /// [`HELPERJS`]: index.html#emscripten-helper-global
+    pub fn kind(&self) -> JSKind {
+        match self.repr {
+            Repr::Number => JSKind::Number,
+            Repr::Bool => JSKind::Bool,
+            Repr::Handle => JSKind::from_code(js_int!("return HELPERJS.typeOf($0);", self)),
+        }
+    }
+
+    /// Converts the JavaScript value referred to by this object into a [`JSValue`] matching its type.
+    ///
+    /// [`JSValue`]: enum.JSValue.html
+    pub fn to_value(&self) -> JSValue {
+        match self.kind() {
+            JSKind::Undefined => JSValue::Undefined,
+            JSKind::Null => JSValue::Null,
+            JSKind::Bool => JSValue::Bool(bool::from(self.clone())),
+            JSKind::Number => JSValue::Number(f64::from(self.clone())),
+            JSKind::String => JSValue::String(String::from(self.clone())),
+            JSKind::BigInt => JSValue::BigInt(self.clone()),
+            JSKind::Symbol => JSValue::Symbol(self.clone()),
+            JSKind::Array => JSValue::Array(self.clone()),
+            JSKind::Function => JSValue::Function(self.clone()),
+            JSKind::Object => JSValue::Object(self.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_of_values_passed_by_value() {
+        assert_eq!(JSObject::from(1.5).kind(), JSKind::Number);
+        assert_eq!(JSObject::from(0u8).kind(), JSKind::Number);
+        assert_eq!(JSObject::from(true).kind(), JSKind::Bool);
+        assert_eq!(JSObject::from(false).kind(), JSKind::Bool);
+    }
+
+    #[test]
+    fn value_of_values_passed_by_value() {
+        assert!(matches!(JSObject::from(2).to_value(), JSValue::Number(n) if n == 2.0));
+        assert!(matches!(JSObject::from(true).to_value(), JSValue::Bool(true)));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{scope, JSError, JSObject};
+
+/// A weak reference to a JavaScript object.
+///
+/// Unlike a [`JSObject`], a `JSWeak` does not keep its target alive: the [`HELPERJS`] object table
+/// only holds a JavaScript `WeakRef` to it. This makes it possible to cache JavaScript objects, such
+/// as DOM nodes, on the Rust side without preventing them from being garbage collected.
+///
+/// Requires a browser that supports `WeakRef`.
+///
+/// [`JSObject`]: struct.JSObject.html
+/// [`HELPERJS`]: index.html#emscripten-helper-global
+#[derive(Debug, Clone)]
+pub struct JSWeak {
+    weak_ref: JSObject,
+}
+
+impl JSWeak {
+    // Wraps a handle to a `WeakRef`. The weak reference is usually kept beyond the scope that created it,
+    // so it is not released with that scope.
+    fn new(weak_ref: JSObject) -> JSWeak {
+        scope::release_on_drop(&weak_ref);
+        JSWeak { weak_ref }
+    }
+
+    /// Returns a strong reference to the target, or `None` if it has been garbage collected.
+    #[track_caller]
+    pub fn upgrade(&self) -> Option<JSObject> {
+        // 0 is never a valid handle, so it can stand for a collected target.
+        let handle = js_int!("var target = $0.deref(); return target === undefined ? 0 : HELPERJS.storeObject(target);",
+                             &self.weak_ref) as u32;
+        if handle == 0 {
+            None
+        } else {
+            Some(JSObject::__from_handle(handle))
+        }
+    }
+}
+
+/// Weak references and finalization.
+impl JSObject {
+    /// Creates a weak reference to this object.
+    ///
+    /// Fails with a `TypeError` if this is not an object or a function, since JavaScript does not allow weak
+    /// references to primitive values.
+    #[track_caller]
+    pub fn downgrade(&self) -> Result<JSWeak, JSError> {
+        js_try_obj!("return HELPERJS.makeWeak($0);", self).map(JSWeak::new)
+    }
+
+    /// Registers `callback` to be called after this object has been garbage collected.
+    ///
+    /// The callback must not hold a [`JSObject`] referring to this object, or it will never be collected.
+    /// JavaScript does not guarantee that finalizers run at all, for example when the page is closed.
+    ///
+    /// Fails with a `TypeError` if this is not an object or a function. Requires a browser that supports
+    /// `FinalizationRegistry`, and the module must export `_emscripten_helper_run_finalizer`, for example
+    /// by linking with `-sEXPORTED_FUNCTIONS=_main,_emscripten_helper_run_finalizer`.
+    ///
+    /// [`JSObject`]: struct.JSObject.html
+    pub fn register_finalizer<F: FnOnce() + 'static>(&self, callback: F) -> Result<(), JSError> {
+        let callback: Box<Box<dyn FnOnce()>> = Box::new(Box::new(callback));
+        let callback_ptr = Box::into_raw(callback);
+        let result = js_try!("HELPERJS.registerFinalizer($0, $1);", self, callback_ptr as usize);
+        if result.is_err() {
+            drop(unsafe { Box::from_raw(callback_ptr) });
+        }
+        result
+    }
+}
+
+/// Runs a callback registered with [`JSObject::register_finalizer`].
+///
+/// **Should not be used directly.** It is only public so that it can be exported to JavaScript.
+///
+/// # Safety
+///
+/// `callback` must be a pointer passed to `HELPERJS.registerFinalizer` by [`JSObject::register_finalizer`]
+/// that has not been run yet. The `FinalizationRegistry` of `HELPERJS` calls this exactly once per
+/// registration. Calling it in any other way is a use after free or a double free.
+///
+/// [`JSObject::register_finalizer`]: struct.JSObject.html#method.register_finalizer
+#[doc(hidden)]
+#[no_mangle]
+pub unsafe extern "C" fn emscripten_helper_run_finalizer(callback: *mut Box<dyn FnOnce()>) {
+    let callback = Box::from_raw(callback);
+    callback();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weak_references_outlive_scopes() {
+        let weak = scope::scope(|_| JSWeak::new(JSObject::__from_handle(9)));
+        assert!(weak.weak_ref.refcount.get(), "the scope must not release the WeakRef");
+        // Dropping it would release the handle in JavaScript.
+        std::mem::forget(weak);
+    }
+}
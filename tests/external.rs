@@ -0,0 +1,115 @@
+//! Checks that the macros of this crate expand in another crate, called by their path without importing
+//! anything else from this crate. The functions are only compiled, since running them needs emscripten.
+
+#![allow(dead_code)]
+
+// Registering the modules declared below keeps their install functions, and the asm-const calls they make,
+// in the test binary. The emscripten runtime is missing on the host, so these stand in for it.
+#[cfg(not(target_os = "emscripten"))]
+mod host {
+    #[no_mangle]
+    extern "C" fn emscripten_asm_const_int() -> std::os::raw::c_int {
+        unreachable!("JavaScript is not available in host tests")
+    }
+}
+
+fn snippets(obj: &emscripten_helper_rs::JSObject) {
+    emscripten_helper_rs::js!("console.log($0);", obj);
+    let _: i32 = emscripten_helper_rs::js_int!("return $0 + 1;", 1);
+    let _: f64 = emscripten_helper_rs::js_double!("return $0 / 2;", 1.5);
+    let _: emscripten_helper_rs::JSObject = emscripten_helper_rs::js_obj!("return [$0];", obj);
+    let _: String = emscripten_helper_rs::js_string!("return String($0);", obj);
+    let _: bool = emscripten_helper_rs::js_as!(bool, "return $0 > 1;", 2);
+    emscripten_helper_rs::js!("console.log($0, $1, $2);", vec![1.5f32], vec!["a"], vec![obj.clone()]);
+}
+
+fn snippets_ending_with_a_comment(obj: &emscripten_helper_rs::JSObject) -> Result<(), emscripten_helper_rs::JSError> {
+    let _: emscripten_helper_rs::JSObject = emscripten_helper_rs::js_obj!("return [$0]; // wrapped", obj);
+    let _: String = emscripten_helper_rs::js_string!("return String($0); // converted", obj);
+    let _: emscripten_helper_rs::JSObject = emscripten_helper_rs::js_try_obj!("return $0.next; // may throw", obj)?;
+    let _: String = emscripten_helper_rs::js_try_string!("return $0.title; // may throw", obj)?;
+    Ok(())
+}
+
+// A type converted with `From` only, without implementing `RefIntoJs`.
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+impl From<Point> for emscripten_helper_rs::JSObject {
+    fn from(point: Point) -> Self {
+        emscripten_helper_rs::js_obj!("return {x: $0, y: $1};", point.x, point.y)
+    }
+}
+
+fn vecs_of_user_types() {
+    let _ = emscripten_helper_rs::JSObject::from(vec![Point { x: 1.0, y: 2.0 }]);
+    emscripten_helper_rs::js!("console.log($0);", vec![Point { x: 3.0, y: 4.0 }]);
+}
+
+fn named_snippets(obj: &emscripten_helper_rs::JSObject) {
+    emscripten_helper_rs::js!("console.log({obj});", obj = obj);
+    let _: String = emscripten_helper_rs::js_string!("return {obj}.name;", obj = obj);
+}
+
+fn try_snippets(obj: &emscripten_helper_rs::JSObject) -> Result<(), emscripten_helper_rs::JSError> {
+    emscripten_helper_rs::js_try!("$0.run();", obj)?;
+    let _: i32 = emscripten_helper_rs::js_try_int!("return $0.length;", obj)?;
+    let _: f64 = emscripten_helper_rs::js_try_double!("return $0.width;", obj)?;
+    let _: emscripten_helper_rs::JSObject = emscripten_helper_rs::js_try_obj!("return $0.next;", obj)?;
+    let _: String = emscripten_helper_rs::js_try_string!("return $0.title;", obj)?;
+    Ok(())
+}
+
+#[emscripten_helper_rs::js_function("return a + b;")]
+fn add(a: f64, b: f64) -> f64;
+
+#[emscripten_helper_rs::js_function("return name.toUpperCase();")]
+fn shout(name: &str) -> String;
+
+#[emscripten_helper_rs::js_function("return x > 0;")]
+fn positive(x: i32) -> bool;
+
+#[emscripten_helper_rs::js_function("return JSON.parse(text);")]
+fn parse(text: &str) -> Result<emscripten_helper_rs::JSObject, emscripten_helper_rs::JSError>;
+
+#[emscripten_helper_rs::js_function]
+extern "JS" {
+    #[js_name = "console.log"]
+    fn log(msg: &str);
+}
+
+emscripten_helper_rs::js_module!(geometry {
+    fn length(x, y) { return Math.sqrt(x * x + y * y); }
+});
+
+mod utils {
+    emscripten_helper_rs::js_module!(pub shapes {
+        fn area(w, h) { return w * h; } // no semicolon needed
+        fn perimeter(w, h) { "return 2 * (w + h); // of a rectangle" }
+    });
+}
+
+// Bodies written as string literals, as procedural macros generating modules must write them.
+macro_rules! generated_module {
+    ( $name:ident ) => {
+        emscripten_helper_rs::js_module!($name {
+            fn twice(x) { "return 2 * x;" }
+        });
+    };
+}
+
+generated_module!(generated);
+
+#[test]
+fn modules_are_registered_by_name() {
+    assert_eq!(geometry::NAME, "geometry");
+    assert_eq!(utils::shapes::NAME, "shapes");
+    let mut registered = emscripten_helper_rs::__registered_modules();
+    registered.sort_unstable();
+    assert_eq!(registered, ["generated", "geometry", "shapes"]);
+}
+
+#[emscripten_helper_rs::js_function("return x * 2; // doubled")]
+fn double(x: f64) -> f64;